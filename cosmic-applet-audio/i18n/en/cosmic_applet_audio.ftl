@@ -1,7 +1,24 @@
 output = Output
 input = Input
 show-media-controls = Show Media Controls on Top Panel
+auto-switch-devices = Switch to Connected Devices
 sound-settings = Sound Settings...
 disconnected = PulseAudio Disconnected
 no-device = No device selected
+hide-device = Hide Device
+show-hidden-devices = Show Hidden Devices
 unknown-artist = Unknown
+playback = Playback
+recording = Recording
+unknown-application = Unknown Application
+microphone-in-use = Microphone in use
+port = Port
+profile = Profile
+show-player = Show Player
+quit-player = Quit Player
+balance = Balance
+left = Left
+right = Right
+fade = Fade
+front = Front
+rear = Rear
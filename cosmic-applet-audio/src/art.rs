@@ -0,0 +1,428 @@
+//! Album art for MPRIS players that is given as a remote or `data:` URL.
+//!
+//! Art is fetched into an on-disk cache, keyed by a hash of its URL, so the popup can show it
+//! like a local file. Downloading `http` and `https` art needs the `remote-art` feature.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+/// Largest image that will be downloaded or decoded.
+pub const MAX_ART_SIZE: usize = 4 * 1024 * 1024;
+/// How long a download may take before it is abandoned.
+#[cfg(feature = "remote-art")]
+pub const FETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Number of images kept in the cache. The least recently used images are evicted first.
+pub const MAX_CACHE_ENTRIES: usize = 64;
+
+/// Limits on what an `ArtCache` fetches and keeps.
+#[derive(Debug, Clone, Copy)]
+pub struct ArtLimits {
+    /// Largest image that will be downloaded or decoded.
+    pub max_size: usize,
+    /// How long a download may take before it is abandoned.
+    #[cfg(feature = "remote-art")]
+    pub fetch_timeout: std::time::Duration,
+    /// Number of images kept in the cache.
+    pub max_entries: usize,
+}
+
+impl Default for ArtLimits {
+    fn default() -> Self {
+        Self {
+            max_size: MAX_ART_SIZE,
+            #[cfg(feature = "remote-art")]
+            fetch_timeout: FETCH_TIMEOUT,
+            max_entries: MAX_CACHE_ENTRIES,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ArtError {
+    Io(io::Error),
+    #[cfg(feature = "remote-art")]
+    Http(Box<ureq::Error>),
+    /// The art is larger than the given size limit.
+    TooLarge(usize),
+    InvalidDataUrl,
+    UnsupportedScheme(String),
+}
+
+impl fmt::Display for ArtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            #[cfg(feature = "remote-art")]
+            Self::Http(err) => write!(f, "{}", err),
+            Self::TooLarge(max_size) => write!(f, "art is larger than {} bytes", max_size),
+            Self::InvalidDataUrl => write!(f, "invalid data URL"),
+            Self::UnsupportedScheme(scheme) => write!(f, "unsupported URL scheme {}", scheme),
+        }
+    }
+}
+
+impl std::error::Error for ArtError {}
+
+impl From<io::Error> for ArtError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(feature = "remote-art")]
+impl From<ureq::Error> for ArtError {
+    fn from(err: ureq::Error) -> Self {
+        Self::Http(Box::new(err))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArtCache {
+    dir: PathBuf,
+    limits: ArtLimits,
+}
+
+impl Default for ArtCache {
+    /// A cache in `$XDG_CACHE_HOME/cosmic-applet-audio/art`.
+    fn default() -> Self {
+        let cache_dir = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+        Self::new(cache_dir.join("cosmic-applet-audio").join("art"))
+    }
+}
+
+impl ArtCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self::with_limits(dir, ArtLimits::default())
+    }
+
+    pub fn with_limits(dir: PathBuf, limits: ArtLimits) -> Self {
+        Self { dir, limits }
+    }
+
+    /// Whether the art at `url` can be fetched into the cache.
+    pub fn supports(url: &Url) -> bool {
+        match url.scheme() {
+            "http" | "https" => cfg!(feature = "remote-art"),
+            scheme => scheme == "data",
+        }
+    }
+
+    /// Path of the cached art for `url`, if it has been fetched before.
+    pub fn cached(&self, url: &Url) -> Option<PathBuf> {
+        let path = self.path(url);
+        path.is_file().then_some(path)
+    }
+
+    /// Fetch the art at `url` into the cache, returning its path.
+    pub async fn get(&self, url: &Url) -> Result<PathBuf, ArtError> {
+        if let Some(path) = self.cached(url) {
+            // Mark the entry as recently used, so it is evicted last
+            if let Err(err) = fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SystemTime::now()))
+            {
+                tracing::warn!("Failed to touch cached art {}: {}", path.display(), err);
+            }
+            return Ok(path);
+        }
+
+        let data = match url.scheme() {
+            #[cfg(feature = "remote-art")]
+            "http" | "https" => {
+                let url = url.clone();
+                let limits = self.limits;
+                tokio::task::spawn_blocking(move || download(&url, &limits))
+                    .await
+                    .map_err(io::Error::other)??
+            }
+            "data" => decode_data_url(url.as_str(), self.limits.max_size)?,
+            scheme => return Err(ArtError::UnsupportedScheme(scheme.to_string())),
+        };
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.path(url);
+        // Write to a temporary file first, so a partial image is never picked up
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, data).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+
+        let cache = self.clone();
+        if let Ok(Err(err)) = tokio::task::spawn_blocking(move || cache.evict()).await {
+            tracing::warn!("Failed to evict cached art: {}", err);
+        }
+        Ok(path)
+    }
+
+    fn path(&self, url: &Url) -> PathBuf {
+        let hash = Sha256::digest(url.as_str().as_bytes());
+        let name: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(name)
+    }
+
+    // Remove the least recently used entries beyond `limits.max_entries`
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let modified = entry.metadata()?.modified()?;
+            entries.push((modified, entry.path()));
+        }
+        if entries.len() <= self.limits.max_entries {
+            return Ok(());
+        }
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.0));
+        for (_, path) in entries.into_iter().skip(self.limits.max_entries) {
+            remove_entry(&path)?;
+        }
+        Ok(())
+    }
+}
+
+// Blocking, so it is run on tokio's blocking pool
+#[cfg(feature = "remote-art")]
+fn download(url: &Url, limits: &ArtLimits) -> Result<Vec<u8>, ArtError> {
+    use std::io::Read;
+
+    let max_size = limits.max_size;
+    let agent = ureq::AgentBuilder::new()
+        .timeout(limits.fetch_timeout)
+        .build();
+    let response = agent.get(url.as_str()).call()?;
+    if response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok())
+        .is_some_and(|len| len > max_size as u64)
+    {
+        return Err(ArtError::TooLarge(max_size));
+    }
+
+    // Read one byte past the limit, to tell a full-sized image from a truncated one
+    let mut data = Vec::new();
+    response
+        .into_reader()
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut data)?;
+    if data.len() > max_size {
+        return Err(ArtError::TooLarge(max_size));
+    }
+    Ok(data)
+}
+
+fn remove_entry(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Decode the payload of a `data:[<mediatype>][;base64],<data>` URL that is at most `max_size`
+/// bytes.
+pub fn decode_data_url(url: &str, max_size: usize) -> Result<Vec<u8>, ArtError> {
+    let (header, payload) = url
+        .strip_prefix("data:")
+        .and_then(|url| url.split_once(','))
+        .ok_or(ArtError::InvalidDataUrl)?;
+
+    let data = if header.ends_with(";base64") {
+        // Base64 grows data by a third, so oversized payloads can be rejected up front
+        if payload.len() / 4 * 3 > max_size {
+            return Err(ArtError::TooLarge(max_size));
+        }
+        let payload = urlencoding::decode_binary(payload.as_bytes());
+        let payload: Vec<u8> = payload
+            .iter()
+            .copied()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(payload)
+            .map_err(|_| ArtError::InvalidDataUrl)?
+    } else {
+        urlencoding::decode_binary(payload.as_bytes()).into_owned()
+    };
+
+    if data.len() > max_size {
+        return Err(ArtError::TooLarge(max_size));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "remote-art")]
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+        time::Duration,
+    };
+
+    // Serve one request on 127.0.0.1, answering it with `response` after `delay`
+    #[cfg(feature = "remote-art")]
+    fn serve(response: Vec<u8>, delay: Duration) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            thread::sleep(delay);
+            let _ = reader.get_mut().write_all(&response);
+        });
+        Url::parse(&format!("http://{}/art.png", addr)).unwrap()
+    }
+
+    #[cfg(feature = "remote-art")]
+    fn ok_response(body: &[u8], content_length: bool) -> Vec<u8> {
+        let mut response = b"HTTP/1.1 200 OK\r\nConnection: close\r\n".to_vec();
+        if content_length {
+            response.extend(format!("Content-Length: {}\r\n", body.len()).into_bytes());
+        }
+        response.extend(b"\r\n");
+        response.extend(body);
+        response
+    }
+
+    fn cache(dir: &Path, limits: ArtLimits) -> ArtCache {
+        ArtCache::with_limits(dir.join("art"), limits)
+    }
+
+    #[cfg(feature = "remote-art")]
+    #[tokio::test]
+    async fn fetch_is_cached_under_url_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), ArtLimits::default());
+        let url = serve(ok_response(b"image", true), Duration::ZERO);
+
+        let path = cache.get(&url).await.unwrap();
+        let hash: String = Sha256::digest(url.as_str().as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(path, dir.path().join("art").join(hash));
+        assert_eq!(fs::read(&path).unwrap(), b"image");
+        assert_eq!(cache.cached(&url), Some(path));
+    }
+
+    #[cfg(feature = "remote-art")]
+    #[tokio::test]
+    async fn oversize_fetch_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = ArtLimits {
+            max_size: 4,
+            ..ArtLimits::default()
+        };
+        let cache = cache(dir.path(), limits);
+
+        // Rejected by its `Content-Length`, and by its body when it has no length
+        for content_length in [true, false] {
+            let url = serve(ok_response(b"image", content_length), Duration::ZERO);
+            assert!(matches!(cache.get(&url).await, Err(ArtError::TooLarge(4))));
+            assert_eq!(cache.cached(&url), None);
+        }
+    }
+
+    #[cfg(feature = "remote-art")]
+    #[tokio::test]
+    async fn stalled_fetch_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = ArtLimits {
+            fetch_timeout: Duration::from_millis(100),
+            ..ArtLimits::default()
+        };
+        let cache = cache(dir.path(), limits);
+        let url = serve(ok_response(b"image", true), Duration::from_secs(5));
+
+        let start = std::time::Instant::now();
+        assert!(matches!(cache.get(&url).await, Err(ArtError::Http(_))));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(cache.cached(&url), None);
+    }
+
+    #[tokio::test]
+    async fn eviction_drops_oldest_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), ArtLimits::default());
+        let urls: Vec<Url> = (0..MAX_CACHE_ENTRIES + 2)
+            .map(|i| Url::parse(&format!("data:,entry{}", i)).unwrap())
+            .collect();
+
+        // Give every entry a distinct age, the first one being the oldest
+        let start = SystemTime::now() - std::time::Duration::from_secs(3600);
+        for (i, url) in urls.iter().enumerate() {
+            let path = cache.get(url).await.unwrap();
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(start + std::time::Duration::from_secs(i as u64))
+                .unwrap();
+        }
+
+        assert_eq!(
+            fs::read_dir(dir.path().join("art")).unwrap().count(),
+            MAX_CACHE_ENTRIES
+        );
+        assert_eq!(cache.cached(&urls[0]), None);
+        assert_eq!(cache.cached(&urls[1]), None);
+        assert!(urls[2..].iter().all(|url| cache.cached(url).is_some()));
+    }
+
+    #[test]
+    fn decode_base64() {
+        assert_eq!(
+            decode_data_url("data:image/png;base64,aGVsbG8=", MAX_ART_SIZE).unwrap(),
+            b"hello"
+        );
+        // Percent-encoded padding and line breaks, as some players send them
+        assert_eq!(
+            decode_data_url("data:image/png;base64,aGVs%0AbG8%3D", MAX_ART_SIZE).unwrap(),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn decode_percent_encoded() {
+        assert_eq!(
+            decode_data_url("data:text/plain,a%20b%FF", MAX_ART_SIZE).unwrap(),
+            b"a b\xff"
+        );
+        assert_eq!(decode_data_url("data:,", MAX_ART_SIZE).unwrap(), b"");
+    }
+
+    #[test]
+    fn decode_malformed() {
+        for url in [
+            "data:image/png;base64",
+            "http://example.com/art.png",
+            "data:;base64,a!b=",
+        ] {
+            assert!(matches!(
+                decode_data_url(url, MAX_ART_SIZE),
+                Err(ArtError::InvalidDataUrl)
+            ));
+        }
+        assert!(matches!(
+            decode_data_url("data:,hello", 4),
+            Err(ArtError::TooLarge(4))
+        ));
+        assert!(matches!(
+            decode_data_url("data:;base64,aGVsbG8gd29ybGQ=", 4),
+            Err(ArtError::TooLarge(4))
+        ));
+    }
+}
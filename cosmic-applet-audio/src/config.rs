@@ -1,9 +1,39 @@
 use cosmic::cosmic_config::cosmic_config_derive::CosmicConfigEntry;
 use cosmic::cosmic_config::{self, CosmicConfigEntry};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize, CosmicConfigEntry, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, CosmicConfigEntry, PartialEq, Eq)]
 #[version = 1]
 pub struct AudioAppletConfig {
     pub show_media_controls_in_top_panel: bool,
+    /// Percent the volume changes by when scrolling over the panel icon.
+    pub volume_step: u8,
+    /// Allow raising the volume above 100%, up to PulseAudio's UI maximum of 150%.
+    pub allow_overamplification: bool,
+    /// Make newly connected devices the default if they are in the priority lists.
+    pub auto_switch_devices: bool,
+    /// Names of preferred output devices, most preferred first.
+    pub output_priority: Vec<String>,
+    /// Names of preferred input devices, most preferred first.
+    pub input_priority: Vec<String>,
+    /// Names shown instead of a device's description, keyed by device name.
+    pub device_aliases: BTreeMap<String, String>,
+    /// Names of devices left out of the device lists.
+    pub hidden_devices: Vec<String>,
+}
+
+impl Default for AudioAppletConfig {
+    fn default() -> Self {
+        Self {
+            show_media_controls_in_top_panel: false,
+            volume_step: 5,
+            allow_overamplification: false,
+            auto_switch_devices: false,
+            output_priority: Vec::new(),
+            input_priority: Vec::new(),
+            device_aliases: BTreeMap::new(),
+            hidden_devices: Vec::new(),
+        }
+    }
 }
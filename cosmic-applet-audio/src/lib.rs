@@ -1,7 +1,13 @@
 mod localize;
 
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::art::ArtCache;
 use crate::localize::localize;
-use crate::pulse::DeviceInfo;
+use crate::pulse::{CardInfo, DeviceInfo, StreamInfo};
+use crate::volume::{input_icon_name, output_icon_name, percent_to_volume, volume_to_percent};
 use config::AudioAppletConfig;
 use cosmic::app::Command;
 use cosmic::applet::cosmic_panel_config::PanelAnchor;
@@ -16,8 +22,10 @@ use cosmic::cosmic_config::CosmicConfigEntry;
 use cosmic::iced::widget;
 use cosmic::iced::Limits;
 use cosmic::iced::{
-    self,
-    widget::{column, row, slider, text},
+    self, event, keyboard,
+    mouse::{self, ScrollDelta},
+    time,
+    widget::{column, mouse_area, progress_bar, row, slider, text, text_input},
     window, Alignment, Length, Subscription,
 };
 use cosmic::iced_runtime::core::alignment::Horizontal;
@@ -33,20 +41,33 @@ use cosmic_time::{anim, chain, id, once_cell::sync::Lazy, Instant, Timeline};
 use iced::wayland::popup::{destroy_popup, get_popup};
 use iced::widget::container;
 use libpulse_binding::volume::Volume;
-use mpris2_zbus::player::PlaybackStatus;
+use mpris2_zbus::player::{LoopStatus, PlaybackStatus};
 use mpris_subscription::MprisRequest;
 use mpris_subscription::MprisUpdate;
+use mpris_subscription::PlayerStatus;
+use zbus::names::OwnedBusName;
 
+mod art;
 mod config;
 mod mpris_subscription;
+#[cfg(feature = "pipewire")]
+mod pipewire_server;
 mod pulse;
+#[cfg(test)]
+mod test_server;
+mod volume;
 
 static SHOW_MEDIA_CONTROLS: Lazy<id::Toggler> = Lazy::new(id::Toggler::unique);
+static AUTO_SWITCH_DEVICES: Lazy<id::Toggler> = Lazy::new(id::Toggler::unique);
 
 const GO_BACK: &str = "media-skip-backward-symbolic";
 const GO_NEXT: &str = "media-skip-forward-symbolic";
 const PAUSE: &str = "media-playback-pause-symbolic";
 const PLAY: &str = "media-playback-start-symbolic";
+const MICROPHONE_IN_USE: &str = "audio-input-microphone-symbolic";
+
+// Distance a touchpad has to scroll to change the volume by one step
+const PIXELS_PER_LINE: f32 = 20.;
 
 pub fn run() -> cosmic::iced::Result {
     localize();
@@ -61,13 +82,31 @@ pub struct Audio {
     current_input: Option<DeviceInfo>,
     outputs: Vec<DeviceInfo>,
     inputs: Vec<DeviceInfo>,
+    cards: Vec<CardInfo>,
+    playback_streams: Vec<StreamInfo>,
+    recording_streams: Vec<StreamInfo>,
     pulse_state: PulseState,
     icon_name: String,
     input_icon_name: String,
     popup: Option<window::Id>,
     timeline: Timeline,
     config: AudioAppletConfig,
-    player_status: Option<mpris_subscription::PlayerStatus>,
+    players: Vec<PlayerStatus>,
+    /// Player picked in the popup, kept until it closes.
+    selected_player: Option<OwnedBusName>,
+    /// Time of the last media tick, used to extrapolate the playback position.
+    media_time: Option<std::time::Instant>,
+    /// Position in seconds while the seek bar is dragged.
+    seek_position: Option<f64>,
+    /// Art URLs that have been requested from the `ArtCache`.
+    art_requests: HashSet<url::Url>,
+    modifiers: keyboard::Modifiers,
+    /// Scrolled distance in lines that hasn't changed the volume yet.
+    scroll_delta: f32,
+    /// Device whose alias and visibility are being edited.
+    editing_device: Option<String>,
+    alias_input: String,
+    show_hidden_devices: bool,
     token_tx: Option<calloop::channel::Sender<TokenRequest>>,
 }
 
@@ -78,24 +117,11 @@ impl Audio {
     }
 
     fn apply_output_volume(&mut self) {
-        let Some(output) = self.current_output.as_ref() else {
-            self.icon_name = "audio-volume-muted-symbolic".to_string();
-            return;
-        };
-
-        let volume = output.volume.avg();
-        let output_volume = volume_to_percent(volume);
-        if volume.is_muted() {
-            self.icon_name = "audio-volume-muted-symbolic".to_string();
-        } else if output_volume < 0.33 {
-            self.icon_name = "audio-volume-low-symbolic".to_string();
-        } else if output_volume < 0.66 {
-            self.icon_name = "audio-volume-medium-symbolic".to_string();
-        } else if output_volume <= 1.00 {
-            self.icon_name = "audio-volume-high-symbolic".to_string();
-        } else {
-            self.icon_name = "audio-volume-overamplified-symbolic".to_string();
+        self.icon_name = match self.current_output.as_ref() {
+            Some(output) => output_icon_name(volume::max(output.volume.get()), output.mute),
+            None => "audio-volume-muted-symbolic",
         }
+        .to_string();
     }
 
     fn update_input(&mut self, input: Option<DeviceInfo>) {
@@ -104,21 +130,148 @@ impl Audio {
     }
 
     fn apply_input_volume(&mut self) {
-        let Some(input) = self.current_input.as_ref() else {
-            self.input_icon_name = "microphone-sensitivity-muted-symbolic".to_string();
+        self.input_icon_name = match self.current_input.as_ref() {
+            Some(input) => input_icon_name(volume::max(input.volume.get()), input.mute),
+            None => "microphone-sensitivity-muted-symbolic",
+        }
+        .to_string();
+    }
+
+    fn write_config(&self) {
+        if let Ok(helper) = cosmic::cosmic_config::Config::new(
+            <Self as cosmic::Application>::APP_ID,
+            AudioAppletConfig::VERSION,
+        ) {
+            if let Err(err) = self.config.write_entry(&helper) {
+                tracing::error!(?err, "Error writing config");
+            }
+        }
+    }
+
+    // The alias of the device, or its description
+    fn device_name(&self, device: &DeviceInfo) -> String {
+        device
+            .name
+            .as_ref()
+            .and_then(|name| self.config.device_aliases.get(name))
+            .cloned()
+            .unwrap_or_else(|| pretty_name(device.description.clone()))
+    }
+
+    fn is_hidden(&self, device: &DeviceInfo) -> bool {
+        device
+            .name
+            .as_ref()
+            .is_some_and(|name| self.config.hidden_devices.contains(name))
+    }
+
+    // Devices to list in the popup, by name and display name
+    fn device_entries(&self, devices: &[DeviceInfo]) -> Vec<(String, String)> {
+        devices
+            .iter()
+            .filter(|device| self.show_hidden_devices || !self.is_hidden(device))
+            .map(|device| {
+                (
+                    device.name.clone().unwrap_or_default(),
+                    self.device_name(device),
+                )
+            })
+            .collect()
+    }
+
+    // Alias and visibility controls for the device being edited
+    fn device_editor(&self, devices: &[DeviceInfo]) -> Option<(String, Element<'static, Message>)> {
+        let name = self.editing_device.as_ref()?;
+        let device = devices.iter().find(|d| d.name.as_ref() == Some(name))?;
+        let hidden = self.is_hidden(device);
+        let editor = column![
+            container(
+                text_input(&pretty_name(device.description.clone()), &self.alias_input)
+                    .on_input(Message::AliasInput)
+                    .on_paste(Message::AliasInput)
+                    .on_submit(Message::AliasSubmit)
+            )
+            .padding([8, 48]),
+            option_button(
+                fl!("hide-device"),
+                hidden,
+                Message::ToggleDeviceHidden(name.clone())
+            ),
+        ];
+        Some((name.clone(), editor.into()))
+    }
+
+    fn show_hidden_toggle(&self, devices: &[DeviceInfo]) -> Vec<Element<'static, Message>> {
+        if devices.iter().any(|device| self.is_hidden(device)) {
+            vec![option_button(
+                fl!("show-hidden-devices"),
+                self.show_hidden_devices,
+                Message::ToggleShowHiddenDevices,
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Make the most preferred available output the default, if auto-switching is enabled
+    fn apply_output_priority(&mut self) {
+        if !self.config.auto_switch_devices {
+            return;
+        }
+        let Some(output) = preferred_device(&self.config.output_priority, &self.outputs) else {
             return;
         };
+        if self.current_output.as_ref().and_then(|o| o.name.as_ref()) == output.name.as_ref() {
+            return;
+        }
+        let output = output.clone();
+        if let Some(conn) = self.pulse_state.connection() {
+            conn.send(pulse::Message::SetDefaultSink(output));
+        }
+    }
+
+    fn apply_input_priority(&mut self) {
+        if !self.config.auto_switch_devices {
+            return;
+        }
+        let Some(input) = preferred_device(&self.config.input_priority, &self.inputs) else {
+            return;
+        };
+        if self.current_input.as_ref().and_then(|i| i.name.as_ref()) == input.name.as_ref() {
+            return;
+        }
+        let input = input.clone();
+        if let Some(conn) = self.pulse_state.connection() {
+            conn.send(pulse::Message::SetDefaultSource(input));
+        }
+    }
 
-        let volume = input.volume.avg();
-        let input_volume = volume_to_percent(volume);
-        if volume.is_muted() || input_volume == 0.0 {
-            self.input_icon_name = "microphone-sensitivity-muted-symbolic".to_string();
-        } else if input_volume < 0.33 {
-            self.input_icon_name = "microphone-sensitivity-low-symbolic".to_string();
-        } else if input_volume < 0.66 {
-            self.input_icon_name = "microphone-sensitivity-medium-symbolic".to_string();
+    // Highest volume the sliders and scrolling can set, in percent
+    fn max_volume(&self) -> f64 {
+        if self.config.allow_overamplification {
+            volume_to_percent(Volume::UI_MAX)
         } else {
-            self.input_icon_name = "microphone-sensitivity-high-symbolic".to_string();
+            100.
+        }
+    }
+
+    fn send_output_volume(&mut self) {
+        if let PulseState::Connected(connection) = &mut self.pulse_state {
+            if let Some(device) = &self.current_output {
+                if let Some(name) = &device.name {
+                    connection.send(pulse::Message::SetSinkVolumeByName(
+                        name.clone(),
+                        device.volume,
+                    ))
+                }
+            }
+        }
+    }
+
+    fn streams_mut(&mut self, kind: StreamKind) -> &mut Vec<StreamInfo> {
+        match kind {
+            StreamKind::Playback => &mut self.playback_streams,
+            StreamKind::Recording => &mut self.recording_streams,
         }
     }
 }
@@ -128,35 +281,67 @@ enum IsOpen {
     None,
     Output,
     Input,
+    Stream(StreamKind, u32),
+}
+
+/// Application streams, which are sink inputs or source outputs in pulse terms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    Playback,
+    Recording,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     SetOutputVolume(f64),
     SetInputVolume(f64),
+    WheelScrolled(ScrollDelta),
+    ModifiersChanged(keyboard::Modifiers),
+    ToggleOutputMute,
+    ToggleInputMute,
     OutputToggle,
     InputToggle,
     OutputChanged(String),
     InputChanged(String),
+    SetOutputBalance(f32),
+    SetOutputFade(f32),
+    OutputPortChanged(String),
+    InputPortChanged(String),
+    CardProfileChanged(u32, String),
+    SetStreamVolume(StreamKind, u32, f64),
+    ToggleStreamMute(StreamKind, u32),
+    StreamToggle(StreamKind, u32),
+    StreamChanged(StreamKind, u32, String),
+    EditDevice(String),
+    AliasInput(String),
+    AliasSubmit,
+    ToggleDeviceHidden(String),
+    ToggleShowHiddenDevices,
+    KillRecording(u32),
     Pulse(pulse::Event),
     TogglePopup,
     CloseRequested(window::Id),
     ToggleMediaControlsInTopPanel(chain::Toggler, bool),
+    ToggleAutoSwitchDevices(chain::Toggler, bool),
     Frame(Instant),
     ConfigChanged(AudioAppletConfig),
     Mpris(mpris_subscription::MprisUpdate),
     MprisRequest(MprisRequest),
+    SelectPlayer(OwnedBusName),
+    ArtFetched(url::Url, Option<PathBuf>),
+    MediaTick(std::time::Instant),
+    Seek(f64),
+    SeekRelease,
     Token(TokenUpdate),
     OpenSettings,
 }
 
 impl Audio {
     fn playback_buttons(&self) -> Option<Element<Message>> {
-        if self.player_status.is_some() && self.config.show_media_controls_in_top_panel {
+        if self.player_status().is_some() && self.config.show_media_controls_in_top_panel {
             let mut elements = Vec::with_capacity(3);
             if self
-                .player_status
-                .as_ref()
+                .player_status()
                 .map(|s| s.can_go_previous)
                 .unwrap_or_default()
             {
@@ -182,8 +367,7 @@ impl Audio {
                 );
             }
             if self
-                .player_status
-                .as_ref()
+                .player_status()
                 .map(|s| s.can_go_next)
                 .unwrap_or_default()
             {
@@ -210,7 +394,7 @@ impl Audio {
     }
 
     fn go_previous(&self, icon_size: u16) -> Option<Element<Message>> {
-        self.player_status.as_ref().and_then(|s| {
+        self.player_status().and_then(|s| {
             if s.can_go_previous {
                 Some(
                     button::icon(icon::from_name(GO_BACK).size(icon_size).symbolic(true))
@@ -226,7 +410,7 @@ impl Audio {
     }
 
     fn go_next(&self, icon_size: u16) -> Option<Element<Message>> {
-        self.player_status.as_ref().and_then(|s| {
+        self.player_status().and_then(|s| {
             if s.can_go_next {
                 Some(
                     button::icon(icon::from_name(GO_NEXT).size(icon_size).symbolic(true))
@@ -241,8 +425,270 @@ impl Audio {
         })
     }
 
+    // Balance and fade sliders for the output, if its channels allow them
+    fn balance_controls(&self) -> Vec<Element<'static, Message>> {
+        let Some(output) = self.current_output.as_ref() else {
+            return Vec::new();
+        };
+        let map = output.channel_map.get();
+        let volumes = output.volume.get();
+        let mut controls = Vec::new();
+        if volume::can_balance(map) {
+            controls.push(balance_slider(
+                fl!("balance"),
+                fl!("left"),
+                fl!("right"),
+                volume::balance(map, volumes),
+                Message::SetOutputBalance,
+            ));
+        }
+        if volume::can_fade(map) {
+            controls.push(balance_slider(
+                fl!("fade"),
+                fl!("front"),
+                fl!("rear"),
+                volume::fade(map, volumes),
+                Message::SetOutputFade,
+            ));
+        }
+        controls
+    }
+
+    // Available ports of a device, and profiles of its card, shown in the device's revealer
+    fn device_options(
+        &self,
+        device: Option<&DeviceInfo>,
+        port_changed: fn(String) -> Message,
+    ) -> Vec<Element<'static, Message>> {
+        let Some(device) = device else {
+            return Vec::new();
+        };
+        let mut options = Vec::new();
+
+        let ports: Vec<_> = device.ports.iter().filter(|p| p.available).collect();
+        if ports.len() > 1 {
+            options.push(option_heading(fl!("port")));
+            for port in ports {
+                options.push(option_button(
+                    port.description
+                        .clone()
+                        .unwrap_or_else(|| port.name.clone()),
+                    device.active_port.as_ref() == Some(&port.name),
+                    port_changed(port.name.clone()),
+                ));
+            }
+        }
+
+        let card = device
+            .card
+            .and_then(|index| self.cards.iter().find(|c| c.index == index));
+        if let Some(card) = card {
+            let profiles: Vec<_> = card.profiles.iter().filter(|p| p.available).collect();
+            if profiles.len() > 1 {
+                options.push(option_heading(fl!("profile")));
+                for profile in profiles {
+                    options.push(option_button(
+                        profile
+                            .description
+                            .clone()
+                            .unwrap_or_else(|| profile.name.clone()),
+                        card.active_profile.as_ref() == Some(&profile.name),
+                        Message::CardProfileChanged(card.index, profile.name.clone()),
+                    ));
+                }
+            }
+        }
+
+        options
+    }
+
+    fn stream_view<'a>(&'a self, kind: StreamKind, stream: &'a StreamInfo) -> Element<'a, Message> {
+        let (devices, icon_name) = match kind {
+            StreamKind::Playback => (
+                &self.outputs,
+                output_icon_name(volume::max(stream.volume.get()), stream.mute),
+            ),
+            StreamKind::Recording => (
+                &self.inputs,
+                input_icon_name(volume::max(stream.volume.get()), stream.mute),
+            ),
+        };
+        let index = stream.index;
+        let volume = volume_to_percent(volume::max(stream.volume.get()));
+        let title = stream
+            .application
+            .clone()
+            .or_else(|| stream.name.clone())
+            .unwrap_or_else(|| fl!("unknown-application"));
+        let device = devices
+            .iter()
+            .find(|d| d.index == stream.device)
+            .map(|d| self.device_name(d))
+            .unwrap_or_default();
+
+        let head = menu_button(
+            row![
+                icon::from_name(
+                    stream
+                        .icon_name
+                        .as_deref()
+                        .unwrap_or("application-x-executable")
+                )
+                .size(24),
+                column![
+                    text(title).width(Length::Fill).size(14),
+                    text(device).size(10),
+                ]
+            ]
+            .spacing(12)
+            .align_items(Alignment::Center),
+        )
+        .on_press(Message::StreamToggle(kind, index));
+
+        let mut content = column![head].width(Length::Fill);
+        if self.is_open == IsOpen::Stream(kind, index) {
+            for device in devices
+                .iter()
+                .filter(|d| self.show_hidden_devices || !self.is_hidden(d))
+            {
+                content = content.push(
+                    menu_button(text(self.device_name(device)).size(14))
+                        .on_press(Message::StreamChanged(
+                            kind,
+                            index,
+                            device.name.clone().unwrap_or_default(),
+                        ))
+                        .width(Length::Fill)
+                        .padding([8, 48]),
+                );
+            }
+        }
+
+        content
+            .push(padded_control(
+                row![
+                    button::icon(icon::from_name(icon_name).size(24).symbolic(true))
+                        .extra_small()
+                        .style(cosmic::theme::Button::AppletIcon)
+                        .on_press(Message::ToggleStreamMute(kind, index)),
+                    slider(0.0..=self.max_volume(), volume, move |v| {
+                        Message::SetStreamVolume(kind, index, v)
+                    })
+                    .breakpoints(&[100.])
+                    .width(Length::FillPortion(5)),
+                    text(format!("{}%", volume.round()))
+                        .size(16)
+                        .width(Length::FillPortion(1))
+                        .horizontal_alignment(Horizontal::Right)
+                ]
+                .spacing(12)
+                .align_items(Alignment::Center),
+            ))
+            .into()
+    }
+
+    // Applications recording from a real input, as opposed to a monitor of an output
+    fn microphone_users(&self) -> impl Iterator<Item = &StreamInfo> {
+        self.recording_streams
+            .iter()
+            .filter(|stream| !stream.monitor && !stream.corked)
+    }
+
+    fn microphone_indicator(&self) -> Option<Element<Message>> {
+        let mut users = self.microphone_users().peekable();
+        users.peek()?;
+
+        let mut content = column![padded_control(text(fl!("microphone-in-use")).size(14))];
+        for stream in users {
+            let index = stream.index;
+            let title = stream
+                .application
+                .clone()
+                .or_else(|| stream.name.clone())
+                .unwrap_or_else(|| fl!("unknown-application"));
+            content = content.push(padded_control(
+                row![
+                    icon::from_name(
+                        stream
+                            .icon_name
+                            .as_deref()
+                            .unwrap_or("application-x-executable")
+                    )
+                    .size(24),
+                    text(title).width(Length::Fill).size(14),
+                    button::icon(
+                        icon::from_name(input_icon_name(
+                            volume::max(stream.volume.get()),
+                            stream.mute
+                        ))
+                        .size(16)
+                        .symbolic(true)
+                    )
+                    .extra_small()
+                    .style(cosmic::theme::Button::AppletIcon)
+                    .on_press(Message::ToggleStreamMute(StreamKind::Recording, index)),
+                    button::icon(
+                        icon::from_name("window-close-symbolic")
+                            .size(16)
+                            .symbolic(true)
+                    )
+                    .extra_small()
+                    .style(cosmic::theme::Button::AppletIcon)
+                    .on_press(Message::KillRecording(index)),
+                ]
+                .spacing(12)
+                .align_items(Alignment::Center),
+            ));
+        }
+        Some(content.into())
+    }
+
+    // The player picked in the popup, or the most active one
+    fn player_status(&self) -> Option<&PlayerStatus> {
+        self.selected_player
+            .as_ref()
+            .and_then(|name| self.players.iter().find(|p| p.name() == name))
+            .or_else(|| mpris_subscription::find_active(&self.players))
+    }
+
+    fn player_switcher(&self) -> Option<Element<Message>> {
+        if self.players.len() < 2 {
+            return None;
+        }
+        let active = self.player_status().map(|s| s.name());
+        let buttons = self
+            .players
+            .iter()
+            .map(|p| {
+                let name = p.identity.clone().unwrap_or_else(|| p.name().to_string());
+                let icon_name = p
+                    .desktop_entry
+                    .as_deref()
+                    .unwrap_or("multimedia-player-symbolic");
+                cosmic::widget::button(
+                    row![icon::from_name(icon_name).size(16), text(name).size(12)]
+                        .spacing(8)
+                        .align_items(Alignment::Center),
+                )
+                .style(if active == Some(p.name()) {
+                    cosmic::theme::Button::Suggested
+                } else {
+                    cosmic::theme::Button::Text
+                })
+                .on_press(Message::SelectPlayer(p.name().to_owned().into()))
+                .into()
+            })
+            .collect();
+        Some(
+            Row::with_children(buttons)
+                .spacing(8)
+                .padding(menu_control_padding())
+                .into(),
+        )
+    }
+
     fn is_play(&self) -> Option<bool> {
-        self.player_status.as_ref().and_then(|s| match s.status {
+        self.player_status().and_then(|s| match s.status {
             PlaybackStatus::Playing => {
                 if s.can_pause {
                     Some(false)
@@ -329,31 +775,29 @@ impl cosmic::Application for Audio {
                         conn.send(pulse::Message::GetDefaultSource);
                         conn.send(pulse::Message::GetSinks);
                         conn.send(pulse::Message::GetSources);
+                        conn.send(pulse::Message::GetSinkInputs);
+                        conn.send(pulse::Message::GetSourceOutputs);
+                        conn.send(pulse::Message::GetCards);
                     }
 
                     return get_popup(popup_settings);
                 }
             }
             Message::SetOutputVolume(vol) => {
-                self.current_output
-                    .as_mut()
-                    .map(|o| o.volume.set(o.volume.len(), percent_to_volume(vol)));
-                self.apply_output_volume();
-                if let PulseState::Connected(connection) = &mut self.pulse_state {
-                    if let Some(device) = &self.current_output {
-                        if let Some(name) = &device.name {
-                            connection.send(pulse::Message::SetSinkVolumeByName(
-                                name.clone(),
-                                device.volume,
-                            ))
-                        }
-                    }
+                if let Some(output) = self.current_output.as_mut() {
+                    volume::scale(output.volume.get_mut(), percent_to_volume(vol));
+                    // The pulse thread mutes the device when the volume is zero, and unmutes it otherwise
+                    output.mute = output.volume.is_muted();
                 }
+                self.apply_output_volume();
+                self.send_output_volume();
             }
             Message::SetInputVolume(vol) => {
-                self.current_input
-                    .as_mut()
-                    .map(|i| i.volume.set(i.volume.len(), percent_to_volume(vol)));
+                if let Some(input) = self.current_input.as_mut() {
+                    volume::scale(input.volume.get_mut(), percent_to_volume(vol));
+                    // The pulse thread mutes the device when the volume is zero, and unmutes it otherwise
+                    input.mute = input.volume.is_muted();
+                }
                 self.apply_input_volume();
                 if let PulseState::Connected(connection) = &mut self.pulse_state {
                     if let Some(device) = &self.current_input {
@@ -367,7 +811,66 @@ impl cosmic::Application for Audio {
                     }
                 }
             }
+            Message::WheelScrolled(delta) => {
+                self.scroll_delta += match delta {
+                    ScrollDelta::Lines { y, .. } => y,
+                    ScrollDelta::Pixels { y, .. } => y / PIXELS_PER_LINE,
+                };
+                let lines = self.scroll_delta.trunc();
+                if lines == 0. {
+                    return Command::none();
+                }
+                self.scroll_delta -= lines;
+
+                let step = lines as f64 * self.config.volume_step as f64;
+                // Scrolling with Ctrl held changes the input volume instead
+                let message = if self.modifiers.control() {
+                    let Some(input) = self.current_input.as_ref() else {
+                        return Command::none();
+                    };
+                    let volume = volume_to_percent(volume::max(input.volume.get())) + step;
+                    Message::SetInputVolume(volume.clamp(0., self.max_volume()))
+                } else {
+                    let Some(output) = self.current_output.as_ref() else {
+                        return Command::none();
+                    };
+                    let volume = volume_to_percent(volume::max(output.volume.get())) + step;
+                    Message::SetOutputVolume(volume.clamp(0., self.max_volume()))
+                };
+                return self.update(message);
+            }
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
+            }
+            Message::ToggleOutputMute => {
+                let Some(output) = self.current_output.as_mut() else {
+                    return Command::none();
+                };
+                output.mute = !output.mute;
+                if let (Some(name), Some(conn)) =
+                    (output.name.clone(), self.pulse_state.connection())
+                {
+                    conn.send(pulse::Message::SetSinkMuteByName(name, output.mute));
+                }
+                self.apply_output_volume();
+            }
+            Message::ToggleInputMute => {
+                let Some(input) = self.current_input.as_mut() else {
+                    return Command::none();
+                };
+                input.mute = !input.mute;
+                if let (Some(name), Some(conn)) =
+                    (input.name.clone(), self.pulse_state.connection())
+                {
+                    conn.send(pulse::Message::SetSourceMuteByName(name, input.mute));
+                }
+                self.apply_input_volume();
+            }
             Message::OutputChanged(val) => {
+                if self.config.auto_switch_devices {
+                    prefer_device(&mut self.config.output_priority, &val);
+                    self.write_config();
+                }
                 if let Some(conn) = self.pulse_state.connection() {
                     if let Some(val) = self.outputs.iter().find(|o| o.name.as_ref() == Some(&val)) {
                         conn.send(pulse::Message::SetDefaultSink(val.clone()));
@@ -375,28 +878,168 @@ impl cosmic::Application for Audio {
                 }
             }
             Message::InputChanged(val) => {
+                if self.config.auto_switch_devices {
+                    prefer_device(&mut self.config.input_priority, &val);
+                    self.write_config();
+                }
                 if let Some(conn) = self.pulse_state.connection() {
                     if let Some(val) = self.inputs.iter().find(|i| i.name.as_ref() == Some(&val)) {
                         conn.send(pulse::Message::SetDefaultSource(val.clone()));
                     }
                 }
             }
+            Message::SetOutputBalance(balance) => {
+                if let Some(output) = self.current_output.as_mut() {
+                    volume::set_balance(output.channel_map.get(), output.volume.get_mut(), balance);
+                }
+                self.send_output_volume();
+            }
+            Message::SetOutputFade(fade) => {
+                if let Some(output) = self.current_output.as_mut() {
+                    volume::set_fade(output.channel_map.get(), output.volume.get_mut(), fade);
+                }
+                self.send_output_volume();
+            }
+            Message::OutputPortChanged(port) => {
+                let Some(output) = self.current_output.as_mut() else {
+                    return Command::none();
+                };
+                output.active_port = Some(port.clone());
+                if let (Some(name), Some(conn)) =
+                    (output.name.clone(), self.pulse_state.connection())
+                {
+                    conn.send(pulse::Message::SetSinkPortByName(name, port));
+                }
+            }
+            Message::InputPortChanged(port) => {
+                let Some(input) = self.current_input.as_mut() else {
+                    return Command::none();
+                };
+                input.active_port = Some(port.clone());
+                if let (Some(name), Some(conn)) =
+                    (input.name.clone(), self.pulse_state.connection())
+                {
+                    conn.send(pulse::Message::SetSourcePortByName(name, port));
+                }
+            }
+            Message::CardProfileChanged(index, profile) => {
+                if let Some(card) = self.cards.iter_mut().find(|c| c.index == index) {
+                    card.active_profile = Some(profile.clone());
+                }
+                if let Some(conn) = self.pulse_state.connection() {
+                    conn.send(pulse::Message::SetCardProfile(index, profile));
+                }
+            }
+            Message::SetStreamVolume(kind, index, vol) => {
+                let Some(stream) = self.streams_mut(kind).iter_mut().find(|s| s.index == index)
+                else {
+                    return Command::none();
+                };
+                volume::scale(stream.volume.get_mut(), percent_to_volume(vol));
+                stream.mute = stream.volume.is_muted();
+                let volume = stream.volume;
+                if let PulseState::Connected(connection) = &mut self.pulse_state {
+                    connection.send(match kind {
+                        StreamKind::Playback => pulse::Message::SetSinkInputVolume(index, volume),
+                        StreamKind::Recording => {
+                            pulse::Message::SetSourceOutputVolume(index, volume)
+                        }
+                    });
+                }
+            }
+            Message::ToggleStreamMute(kind, index) => {
+                let Some(stream) = self.streams_mut(kind).iter_mut().find(|s| s.index == index)
+                else {
+                    return Command::none();
+                };
+                stream.mute = !stream.mute;
+                let mute = stream.mute;
+                if let Some(conn) = self.pulse_state.connection() {
+                    conn.send(match kind {
+                        StreamKind::Playback => pulse::Message::SetSinkInputMute(index, mute),
+                        StreamKind::Recording => pulse::Message::SetSourceOutputMute(index, mute),
+                    });
+                }
+            }
+            Message::StreamToggle(kind, index) => {
+                self.is_open = if self.is_open == IsOpen::Stream(kind, index) {
+                    IsOpen::None
+                } else {
+                    IsOpen::Stream(kind, index)
+                }
+            }
+            Message::StreamChanged(kind, index, device) => {
+                self.is_open = IsOpen::None;
+                if let Some(conn) = self.pulse_state.connection() {
+                    conn.send(match kind {
+                        StreamKind::Playback => pulse::Message::MoveSinkInput(index, device),
+                        StreamKind::Recording => pulse::Message::MoveSourceOutput(index, device),
+                    });
+                }
+            }
+            Message::KillRecording(index) => {
+                self.recording_streams.retain(|s| s.index != index);
+                if let Some(conn) = self.pulse_state.connection() {
+                    conn.send(pulse::Message::KillSourceOutput(index));
+                }
+            }
+            Message::EditDevice(name) => {
+                if self.editing_device.as_ref() == Some(&name) {
+                    self.editing_device = None;
+                } else {
+                    self.alias_input = self
+                        .config
+                        .device_aliases
+                        .get(&name)
+                        .cloned()
+                        .unwrap_or_default();
+                    self.editing_device = Some(name);
+                }
+            }
+            Message::AliasInput(alias) => self.alias_input = alias,
+            Message::AliasSubmit => {
+                let Some(name) = self.editing_device.take() else {
+                    return Command::none();
+                };
+                let alias = self.alias_input.trim();
+                if alias.is_empty() {
+                    self.config.device_aliases.remove(&name);
+                } else {
+                    self.config.device_aliases.insert(name, alias.to_string());
+                }
+                self.write_config();
+            }
+            Message::ToggleDeviceHidden(name) => {
+                if self.config.hidden_devices.contains(&name) {
+                    self.config.hidden_devices.retain(|n| *n != name);
+                } else {
+                    self.config.hidden_devices.push(name);
+                }
+                self.write_config();
+            }
+            Message::ToggleShowHiddenDevices => {
+                self.show_hidden_devices = !self.show_hidden_devices;
+            }
             Message::OutputToggle => {
+                self.editing_device = None;
                 self.is_open = if self.is_open == IsOpen::Output {
                     IsOpen::None
                 } else {
                     if let Some(conn) = self.pulse_state.connection() {
                         conn.send(pulse::Message::GetSinks);
+                        conn.send(pulse::Message::GetCards);
                     }
                     IsOpen::Output
                 }
             }
             Message::InputToggle => {
+                self.editing_device = None;
                 self.is_open = if self.is_open == IsOpen::Input {
                     IsOpen::None
                 } else {
                     if let Some(conn) = self.pulse_state.connection() {
                         conn.send(pulse::Message::GetSources);
+                        conn.send(pulse::Message::GetCards);
                     }
                     IsOpen::Input
                 }
@@ -414,6 +1057,9 @@ impl cosmic::Application for Audio {
                         conn.send(pulse::Message::GetSources);
                         conn.send(pulse::Message::GetDefaultSink);
                         conn.send(pulse::Message::GetDefaultSource);
+                        conn.send(pulse::Message::GetSinkInputs);
+                        conn.send(pulse::Message::GetSourceOutputs);
+                        conn.send(pulse::Message::GetCards);
                     }
                 }
                 pulse::Event::MessageReceived(msg) => {
@@ -438,6 +1084,17 @@ impl cosmic::Application for Audio {
                         pulse::Message::SetDefaultSource(source) => {
                             self.update_input(Some(source));
                         }
+                        pulse::Message::SinkAdded(_) | pulse::Message::SinkRemoved(_) => {
+                            self.apply_output_priority();
+                        }
+                        pulse::Message::SourceAdded(_) | pulse::Message::SourceRemoved(_) => {
+                            self.apply_input_priority();
+                        }
+                        pulse::Message::SetCards(cards) => self.cards = cards,
+                        pulse::Message::SetSinkInputs(inputs) => self.playback_streams = inputs,
+                        pulse::Message::SetSourceOutputs(outputs) => {
+                            self.recording_streams = outputs
+                        }
                         pulse::Message::Disconnected => {
                             panic!("Subscription error handling is bad. This should never happen.")
                         }
@@ -459,13 +1116,21 @@ impl cosmic::Application for Audio {
             Message::ToggleMediaControlsInTopPanel(chain, enabled) => {
                 self.timeline.set_chain(chain).start();
                 self.config.show_media_controls_in_top_panel = enabled;
-                if let Ok(helper) =
-                    cosmic::cosmic_config::Config::new(Self::APP_ID, AudioAppletConfig::VERSION)
-                {
-                    if let Err(err) = self.config.write_entry(&helper) {
-                        tracing::error!(?err, "Error writing config");
+                self.write_config();
+            }
+            Message::ToggleAutoSwitchDevices(chain, enabled) => {
+                self.timeline.set_chain(chain).start();
+                self.config.auto_switch_devices = enabled;
+                if enabled {
+                    // Start from the devices in use, so enabling doesn't switch anything
+                    if let Some(name) = self.current_output.as_ref().and_then(|o| o.name.clone()) {
+                        prefer_device(&mut self.config.output_priority, &name);
+                    }
+                    if let Some(name) = self.current_input.as_ref().and_then(|i| i.name.clone()) {
+                        prefer_device(&mut self.config.input_priority, &name);
                     }
                 }
+                self.write_config();
             }
             Message::CloseRequested(id) => {
                 if Some(id) == self.popup {
@@ -475,17 +1140,78 @@ impl cosmic::Application for Audio {
             Message::ConfigChanged(c) => {
                 self.config = c;
             }
-            Message::Mpris(mpris_subscription::MprisUpdate::Player(p)) => {
-                self.player_status = Some(p);
+            Message::Mpris(mpris_subscription::MprisUpdate::Players(players)) => {
+                self.players = players;
+                if let Some(selected) = self.selected_player.as_ref() {
+                    if !self.players.iter().any(|p| p.name() == selected) {
+                        self.selected_player = None;
+                    }
+                }
+
+                // Fetch remote art once, failed fetches are not retried. Art that is already
+                // cached is requested too, so the cache knows it is still in use.
+                let fetches: Vec<_> = self
+                    .players
+                    .iter()
+                    .filter_map(|p| p.art_url.clone())
+                    .filter(|url| ArtCache::supports(url) && self.art_requests.insert(url.clone()))
+                    .map(|url| {
+                        Command::perform(
+                            async move {
+                                let path = match ArtCache::default().get(&url).await {
+                                    Ok(path) => Some(path),
+                                    Err(err) => {
+                                        tracing::warn!("Failed to fetch art {}: {}", url, err);
+                                        None
+                                    }
+                                };
+                                (url, path)
+                            },
+                            |(url, path)| cosmic::app::message::app(Message::ArtFetched(url, path)),
+                        )
+                    })
+                    .collect();
+                return Command::batch(fetches);
+            }
+            Message::ArtFetched(url, path) => {
+                for player in &mut self.players {
+                    if player.art_url.as_ref() == Some(&url) {
+                        player.icon.clone_from(&path);
+                    }
+                }
             }
             Message::Mpris(MprisUpdate::Finished) => {
-                self.player_status = None;
+                self.players.clear();
+                self.selected_player = None;
             }
-            Message::Mpris(MprisUpdate::Setup) => {
-                self.player_status = None;
+            Message::SelectPlayer(name) => {
+                self.selected_player = Some(name);
+            }
+            Message::MediaTick(now) => {
+                self.media_time = Some(now);
+            }
+            Message::Seek(position) => {
+                self.seek_position = Some(position);
+            }
+            Message::SeekRelease => {
+                let Some(position) = self.seek_position.take() else {
+                    return Command::none();
+                };
+                let position = Duration::from_secs_f64(position);
+                // Show the new position until the player confirms it with `Seeked`
+                let name = self.player_status().map(|s| s.name().to_owned());
+                if let Some(status) = self
+                    .players
+                    .iter_mut()
+                    .find(|p| Some(p.name()) == name.as_ref())
+                {
+                    status.position = Some(position);
+                    status.position_time = std::time::Instant::now();
+                }
+                return self.update(Message::MprisRequest(MprisRequest::SetPosition(position)));
             }
             Message::MprisRequest(r) => {
-                let Some(player_status) = self.player_status.as_ref() else {
+                let Some(player_status) = self.player_status() else {
                     tracing::error!("No player found");
                     return Command::none();
                 };
@@ -516,6 +1242,54 @@ impl cosmic::Application for Audio {
                             tracing::error!("Error playing previous: {}", err);
                         }
                     }),
+                    MprisRequest::SetPosition(position) => {
+                        let Some(track_id) = player_status.track_id.clone() else {
+                            tracing::error!("Player has no track to seek in");
+                            return Command::none();
+                        };
+                        tokio::spawn(async move {
+                            let res = player.set_position(&track_id, position).await;
+                            if let Err(err) = res {
+                                tracing::error!("Error seeking: {}", err);
+                            }
+                        })
+                    }
+                    MprisRequest::SetShuffle(shuffle) => tokio::spawn(async move {
+                        let res = player.set_shuffle(shuffle).await;
+                        if let Err(err) = res {
+                            tracing::error!("Error setting shuffle: {}", err);
+                        }
+                    }),
+                    MprisRequest::SetLoopStatus(loop_status) => tokio::spawn(async move {
+                        let res = player.set_loop_status(loop_status).await;
+                        if let Err(err) = res {
+                            tracing::error!("Error setting loop status: {}", err);
+                        }
+                    }),
+                    MprisRequest::SetVolume(volume) => tokio::spawn(async move {
+                        let res = player.set_volume(volume).await;
+                        if let Err(err) = res {
+                            tracing::error!("Error setting player volume: {}", err);
+                        }
+                    }),
+                    MprisRequest::Raise => {
+                        let media_player = player_status.media_player.clone();
+                        tokio::spawn(async move {
+                            let res = media_player.raise().await;
+                            if let Err(err) = res {
+                                tracing::error!("Error raising player: {}", err);
+                            }
+                        })
+                    }
+                    MprisRequest::Quit => {
+                        let media_player = player_status.media_player.clone();
+                        tokio::spawn(async move {
+                            let res = media_player.quit().await;
+                            if let Err(err) = res {
+                                tracing::error!("Error quitting player: {}", err);
+                            }
+                        })
+                    }
                 };
             }
             Message::OpenSettings => {
@@ -552,7 +1326,36 @@ impl cosmic::Application for Audio {
     }
 
     fn subscription(&self) -> Subscription<Message> {
+        // Only tick while a playing player's position is visible
+        let media_tick = match self.player_status() {
+            Some(s)
+                if self.popup.is_some()
+                    && s.position.is_some()
+                    && matches!(s.status, PlaybackStatus::Playing) =>
+            {
+                time::every(Duration::from_secs(1)).map(Message::MediaTick)
+            }
+            _ => Subscription::none(),
+        };
+
+        // Scrolling changes the volume while the pointer can only be over the panel icon
+        let scroll = if self.popup.is_none() {
+            event::listen_with(|e, _| match e {
+                iced::Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                    Some(Message::WheelScrolled(delta))
+                }
+                iced::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                    Some(Message::ModifiersChanged(modifiers))
+                }
+                _ => None,
+            })
+        } else {
+            Subscription::none()
+        };
+
         Subscription::batch(vec![
+            media_tick,
+            scroll,
             pulse::connect().map(Message::Pulse),
             self.timeline
                 .as_subscription()
@@ -569,26 +1372,37 @@ impl cosmic::Application for Audio {
     }
 
     fn view(&self) -> Element<Message> {
-        let btn = self
-            .core
-            .applet
-            .icon_button(&self.icon_name)
-            .on_press(Message::TogglePopup);
+        let btn = mouse_area(
+            self.core
+                .applet
+                .icon_button(&self.icon_name)
+                .on_press(Message::TogglePopup),
+        )
+        .on_middle_press(Message::ToggleOutputMute);
+        let mut elements = Vec::with_capacity(3);
         if let Some(playback_buttons) = self.playback_buttons() {
-            match self.core.applet.anchor {
-                PanelAnchor::Left | PanelAnchor::Right => {
-                    Column::with_children(vec![playback_buttons, btn.into()])
-                        .align_items(Alignment::Center)
-                        .into()
-                }
-                PanelAnchor::Top | PanelAnchor::Bottom => {
-                    Row::with_children(vec![playback_buttons, btn.into()])
-                        .align_items(Alignment::Center)
-                        .into()
-                }
-            }
-        } else {
-            btn.into()
+            elements.push(playback_buttons);
+        }
+        if self.microphone_users().next().is_some() {
+            elements.push(
+                self.core
+                    .applet
+                    .icon_button(MICROPHONE_IN_USE)
+                    .on_press(Message::TogglePopup)
+                    .into(),
+            );
+        }
+        if elements.is_empty() {
+            return btn.into();
+        }
+        elements.push(btn.into());
+        match self.core.applet.anchor {
+            PanelAnchor::Left | PanelAnchor::Right => Column::with_children(elements)
+                .align_items(Alignment::Center)
+                .into(),
+            PanelAnchor::Top | PanelAnchor::Bottom => Row::with_children(elements)
+                .align_items(Alignment::Center)
+                .into(),
         }
     }
 
@@ -597,16 +1411,36 @@ impl cosmic::Application for Audio {
         let out_f64 = volume_to_percent(
             self.current_output
                 .as_ref()
-                .map(|o| o.volume.avg())
+                .map(|o| volume::max(o.volume.get()))
                 .unwrap_or_default(),
         );
         let in_f64 = volume_to_percent(
             self.current_input
                 .as_ref()
-                .map(|o| o.volume.avg())
+                .map(|o| volume::max(o.volume.get()))
                 .unwrap_or_default(),
         );
 
+        let output_options = if self.is_open == IsOpen::Output {
+            let mut options = self.show_hidden_toggle(&self.outputs);
+            options.extend(self.balance_controls());
+            options.extend(
+                self.device_options(self.current_output.as_ref(), Message::OutputPortChanged),
+            );
+            options
+        } else {
+            Vec::new()
+        };
+        let input_options = if self.is_open == IsOpen::Input {
+            let mut options = self.show_hidden_toggle(&self.inputs);
+            options.extend(
+                self.device_options(self.current_input.as_ref(), Message::InputPortChanged),
+            );
+            options
+        } else {
+            Vec::new()
+        };
+
         let mut audio_content = if audio_disabled {
             column![padded_control(
                 text(fl!("disconnected"))
@@ -618,10 +1452,16 @@ impl cosmic::Application for Audio {
             column![
                 padded_control(
                     row![
-                        icon::from_name(self.icon_name.as_str())
-                            .size(24)
-                            .symbolic(true),
-                        slider(0.0..=100.0, out_f64, Message::SetOutputVolume)
+                        button::icon(
+                            icon::from_name(self.icon_name.as_str())
+                                .size(24)
+                                .symbolic(true)
+                        )
+                        .extra_small()
+                        .style(cosmic::theme::Button::AppletIcon)
+                        .on_press(Message::ToggleOutputMute),
+                        slider(0.0..=self.max_volume(), out_f64, Message::SetOutputVolume)
+                            .breakpoints(&[100.])
                             .width(Length::FillPortion(5)),
                         text(format!("{}%", out_f64.round()))
                             .size(16)
@@ -633,10 +1473,16 @@ impl cosmic::Application for Audio {
                 ),
                 padded_control(
                     row![
-                        icon::from_name(self.input_icon_name.as_str())
-                            .size(24)
-                            .symbolic(true),
-                        slider(0.0..=100.0, in_f64, Message::SetInputVolume)
+                        button::icon(
+                            icon::from_name(self.input_icon_name.as_str())
+                                .size(24)
+                                .symbolic(true)
+                        )
+                        .extra_small()
+                        .style(cosmic::theme::Button::AppletIcon)
+                        .on_press(Message::ToggleInputMute),
+                        slider(0.0..=self.max_volume(), in_f64, Message::SetInputVolume)
+                            .breakpoints(&[100.])
                             .width(Length::FillPortion(5)),
                         text(format!("{}%", in_f64.round()))
                             .size(16)
@@ -647,47 +1493,79 @@ impl cosmic::Application for Audio {
                     .align_items(Alignment::Center)
                 ),
                 padded_control(divider::horizontal::default()),
-                revealer(
-                    self.is_open == IsOpen::Output,
-                    fl!("output"),
-                    match &self.current_output {
-                        Some(output) => pretty_name(output.description.clone()),
-                        None => String::from("No device selected"),
-                    },
-                    self.outputs
-                        .clone()
-                        .into_iter()
-                        .map(|output| (
-                            output.name.clone().unwrap_or_default(),
-                            pretty_name(output.description)
-                        ))
-                        .collect(),
-                    Message::OutputToggle,
-                    Message::OutputChanged,
+                output_options.into_iter().fold(
+                    revealer(
+                        self.is_open == IsOpen::Output,
+                        fl!("output"),
+                        match &self.current_output {
+                            Some(output) => self.device_name(output),
+                            None => String::from("No device selected"),
+                        },
+                        self.device_entries(&self.outputs),
+                        self.device_editor(&self.outputs),
+                        Message::OutputToggle,
+                        Message::OutputChanged,
+                    ),
+                    |col, option| col.push(option)
                 ),
-                revealer(
-                    self.is_open == IsOpen::Input,
-                    fl!("input"),
-                    match &self.current_input {
-                        Some(input) => pretty_name(input.description.clone()),
-                        None => fl!("no-device"),
-                    },
-                    self.inputs
-                        .clone()
-                        .into_iter()
-                        .map(|input| (
-                            input.name.clone().unwrap_or_default(),
-                            pretty_name(input.description)
-                        ))
-                        .collect(),
-                    Message::InputToggle,
-                    Message::InputChanged,
+                input_options.into_iter().fold(
+                    revealer(
+                        self.is_open == IsOpen::Input,
+                        fl!("input"),
+                        match &self.current_input {
+                            Some(input) => self.device_name(input),
+                            None => fl!("no-device"),
+                        },
+                        self.device_entries(&self.inputs),
+                        self.device_editor(&self.inputs),
+                        Message::InputToggle,
+                        Message::InputChanged,
+                    ),
+                    |col, option| col.push(option)
                 )
             ]
             .align_items(Alignment::Start)
         };
 
-        if let Some(s) = self.player_status.as_ref() {
+        if let Some(indicator) = self.microphone_indicator() {
+            audio_content = audio_content
+                .push(padded_control(divider::horizontal::default()))
+                .push(indicator);
+        }
+
+        if !audio_disabled {
+            for (kind, streams, heading) in [
+                (
+                    StreamKind::Playback,
+                    &self.playback_streams,
+                    fl!("playback"),
+                ),
+                (
+                    StreamKind::Recording,
+                    &self.recording_streams,
+                    fl!("recording"),
+                ),
+            ] {
+                if streams.is_empty() {
+                    continue;
+                }
+                audio_content = audio_content
+                    .push(padded_control(divider::horizontal::default()))
+                    .push(padded_control(text(heading).size(14)));
+                for stream in streams {
+                    audio_content = audio_content.push(self.stream_view(kind, stream));
+                }
+            }
+        }
+
+        let player_switcher = self.player_switcher();
+        if player_switcher.is_some() || self.player_status().is_some() {
+            audio_content = audio_content.push(padded_control(divider::horizontal::default()));
+        }
+        if let Some(player_switcher) = player_switcher {
+            audio_content = audio_content.push(player_switcher);
+        }
+        if let Some(s) = self.player_status() {
             let mut elements = Vec::with_capacity(5);
 
             if let Some(icon_path) = s.icon.clone() {
@@ -746,13 +1624,104 @@ impl cosmic::Application for Audio {
                 elements.push(go_next);
             }
 
-            audio_content = audio_content.push(padded_control(divider::horizontal::default()));
             audio_content = audio_content.push(
                 Row::with_children(elements)
                     .align_items(Alignment::Center)
                     .spacing(8)
                     .padding(menu_control_padding()),
             );
+
+            let position = s.position_at(self.media_time.unwrap_or(s.position_time));
+            if let (Some(position), Some(length)) = (position, s.length) {
+                let length = length.as_secs_f64();
+                let position = self.seek_position.unwrap_or(position.as_secs_f64());
+                let bar: Element<_> = if s.can_seek && s.track_id.is_some() {
+                    slider(0.0..=length, position, Message::Seek)
+                        .on_release(Message::SeekRelease)
+                        .into()
+                } else {
+                    progress_bar(0.0..=length as f32, position as f32)
+                        .height(4)
+                        .into()
+                };
+                audio_content = audio_content.push(padded_control(
+                    row![
+                        text(format_time(position)).size(10),
+                        bar,
+                        text(format_time(length)).size(10),
+                    ]
+                    .spacing(8)
+                    .align_items(Alignment::Center),
+                ));
+            }
+
+            // Optional MPRIS features, only shown when the player supports them
+            let mut controls = Vec::new();
+            if s.can_control {
+                if let Some(shuffle) = s.shuffle {
+                    controls.push(media_toggle(
+                        "media-playlist-shuffle-symbolic",
+                        shuffle,
+                        Message::MprisRequest(MprisRequest::SetShuffle(!shuffle)),
+                    ));
+                }
+                if let Some(loop_status) = s.loop_status.as_ref() {
+                    let (icon_name, active, next) = match loop_status {
+                        LoopStatus::None => (
+                            "media-playlist-repeat-symbolic",
+                            false,
+                            LoopStatus::Playlist,
+                        ),
+                        LoopStatus::Playlist => {
+                            ("media-playlist-repeat-symbolic", true, LoopStatus::Track)
+                        }
+                        LoopStatus::Track => (
+                            "media-playlist-repeat-song-symbolic",
+                            true,
+                            LoopStatus::None,
+                        ),
+                    };
+                    controls.push(media_toggle(
+                        icon_name,
+                        active,
+                        Message::MprisRequest(MprisRequest::SetLoopStatus(next)),
+                    ));
+                }
+                if let Some(volume) = s.volume {
+                    controls.push(
+                        icon::from_name(output_icon_name(percent_to_volume(volume * 100.), false))
+                            .size(16)
+                            .symbolic(true)
+                            .into(),
+                    );
+                    controls.push(
+                        slider(0.0..=100.0, volume * 100., |v| {
+                            Message::MprisRequest(MprisRequest::SetVolume(v / 100.))
+                        })
+                        .width(Length::Fill)
+                        .into(),
+                    );
+                }
+            }
+            if !controls.is_empty() {
+                audio_content = audio_content.push(padded_control(
+                    Row::with_children(controls)
+                        .spacing(8)
+                        .align_items(Alignment::Center),
+                ));
+            }
+            if s.can_raise {
+                audio_content = audio_content.push(
+                    menu_button(text(fl!("show-player")).size(14))
+                        .on_press(Message::MprisRequest(MprisRequest::Raise)),
+                );
+            }
+            if s.can_quit {
+                audio_content = audio_content.push(
+                    menu_button(text(fl!("quit-player")).size(14))
+                        .on_press(Message::MprisRequest(MprisRequest::Quit)),
+                );
+            }
         }
         let content = column![
             audio_content,
@@ -770,6 +1739,19 @@ impl cosmic::Application for Audio {
                 .width(Length::Fill)
             )
             .padding([0, 24]),
+            container(
+                anim!(
+                    // toggler
+                    AUTO_SWITCH_DEVICES,
+                    &self.timeline,
+                    Some(fl!("auto-switch-devices")),
+                    self.config.auto_switch_devices,
+                    Message::ToggleAutoSwitchDevices,
+                )
+                .text_size(14)
+                .width(Length::Fill)
+            )
+            .padding([0, 24]),
             padded_control(divider::horizontal::default()),
             menu_button(text(fl!("sound-settings")).size(14)).on_press(Message::OpenSettings)
         ]
@@ -784,11 +1766,25 @@ impl cosmic::Application for Audio {
     }
 }
 
+// The first device in `priority` that is available
+fn preferred_device<'a>(priority: &[String], devices: &'a [DeviceInfo]) -> Option<&'a DeviceInfo> {
+    priority
+        .iter()
+        .find_map(|name| devices.iter().find(|d| d.name.as_ref() == Some(name)))
+}
+
+// Move `name` to the front of `priority`
+fn prefer_device(priority: &mut Vec<String>, name: &str) {
+    priority.retain(|n| n != name);
+    priority.insert(0, name.to_string());
+}
+
 fn revealer(
     open: bool,
     title: String,
     selected: String,
     options: Vec<(String, String)>,
+    mut editor: Option<(String, Element<'static, Message>)>,
     toggle: Message,
     mut change: impl FnMut(String) -> Message + 'static,
 ) -> widget::Column<'static, Message, crate::Theme, Renderer> {
@@ -796,12 +1792,32 @@ fn revealer(
         options.iter().fold(
             column![revealer_head(open, title, selected, toggle)].width(Length::Fill),
             |col, (id, name)| {
-                col.push(
-                    menu_button(text(name).size(14))
-                        .on_press(change(id.clone()))
-                        .width(Length::Fill)
-                        .padding([8, 48]),
-                )
+                let col = col.push(
+                    row![
+                        menu_button(text(name).size(14))
+                            .on_press(change(id.clone()))
+                            .width(Length::Fill)
+                            .padding([8, 0, 8, 48]),
+                        button::icon(
+                            icon::from_name("view-more-symbolic")
+                                .size(16)
+                                .symbolic(true)
+                        )
+                        .extra_small()
+                        .style(cosmic::theme::Button::AppletIcon)
+                        .on_press(Message::EditDevice(id.clone())),
+                    ]
+                    .spacing(8)
+                    .padding([0, 24, 0, 0])
+                    .align_items(Alignment::Center),
+                );
+                match editor.take() {
+                    Some((editing, content)) if editing == *id => col.push(content),
+                    other => {
+                        editor = other;
+                        col
+                    }
+                }
             },
         )
     } else {
@@ -822,6 +1838,80 @@ fn revealer_head(
     .on_press(toggle)
 }
 
+fn option_heading(heading: String) -> Element<'static, Message> {
+    container(text(heading).size(12))
+        .padding([8, 48, 0, 48])
+        .into()
+}
+
+fn option_button(label: String, selected: bool, message: Message) -> Element<'static, Message> {
+    let mut content = vec![text(label).size(14).width(Length::Fill).into()];
+    if selected {
+        content.push(
+            icon::from_name("object-select-symbolic")
+                .size(16)
+                .symbolic(true)
+                .into(),
+        );
+    }
+    menu_button(Row::with_children(content).align_items(Alignment::Center))
+        .on_press(message)
+        .width(Length::Fill)
+        .padding([8, 48])
+        .into()
+}
+
+fn balance_slider(
+    title: String,
+    start: String,
+    end: String,
+    value: f32,
+    on_change: fn(f32) -> Message,
+) -> Element<'static, Message> {
+    column![
+        text(title).size(12),
+        row![
+            text(start).size(10),
+            slider(-1.0..=1.0, value, on_change)
+                .step(0.05)
+                .breakpoints(&[0.])
+                .width(Length::Fill),
+            text(end).size(10),
+        ]
+        .spacing(8)
+        .align_items(Alignment::Center),
+    ]
+    .spacing(4)
+    .padding([8, 48])
+    .into()
+}
+
+fn media_toggle(
+    icon_name: &'static str,
+    active: bool,
+    message: Message,
+) -> Element<'static, Message> {
+    button::icon(icon::from_name(icon_name).size(16).symbolic(true))
+        .extra_small()
+        .style(if active {
+            cosmic::theme::Button::Suggested
+        } else {
+            cosmic::theme::Button::AppletIcon
+        })
+        .on_press(message)
+        .into()
+}
+
+// Formats seconds as "m:ss", or "h:mm:ss" for an hour or longer
+fn format_time(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
 fn pretty_name(name: Option<String>) -> String {
     match name {
         Some(n) => n,
@@ -865,14 +1955,3 @@ impl Default for IsOpen {
     }
 }
 
-fn volume_to_percent(volume: Volume) -> f64 {
-    volume.0 as f64 * 100. / Volume::NORMAL.0 as f64
-}
-
-fn percent_to_volume(percent: f64) -> Volume {
-    Volume(
-        (percent / 100. * Volume::NORMAL.0 as f64)
-            .clamp(0., Volume::NORMAL.0 as f64)
-            .round() as u32,
-    )
-}
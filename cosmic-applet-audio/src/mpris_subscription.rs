@@ -1,13 +1,20 @@
-use std::{borrow::Cow, fmt::Debug, hash::Hash, path::PathBuf};
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    hash::Hash,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use cosmic::{
     iced::{self, subscription},
-    iced_futures::futures::{self, future::OptionFuture, SinkExt, StreamExt},
+    iced_futures::futures::{self, SinkExt, StreamExt},
 };
 use mpris2_zbus::{
     enumerator,
     media_player::MediaPlayer,
-    player::{PlaybackStatus, Player},
+    metadata::TrackID,
+    player::{LoopStatus, PlaybackStatus, Player},
 };
 use tokio::join;
 use urlencoding::decode;
@@ -16,10 +23,17 @@ use zbus::{
     Connection,
 };
 
+use crate::art::ArtCache;
+
 #[derive(Clone, Debug)]
 pub struct PlayerStatus {
     pub player: Player,
+    pub media_player: MediaPlayer,
+    pub identity: Option<String>,
+    pub desktop_entry: Option<String>,
     pub icon: Option<PathBuf>,
+    /// URL of the art, which has to be fetched into the `ArtCache` if `icon` is `None`.
+    pub art_url: Option<url::Url>,
     pub title: Option<Cow<'static, str>>,
     pub artists: Option<Vec<Cow<'static, str>>>,
     pub status: PlaybackStatus,
@@ -27,10 +41,27 @@ pub struct PlayerStatus {
     pub can_play: bool,
     pub can_go_previous: bool,
     pub can_go_next: bool,
+    pub can_seek: bool,
+    pub track_id: Option<TrackID>,
+    pub length: Option<Duration>,
+    /// Position when the status was read, at `position_time`.
+    pub position: Option<Duration>,
+    pub position_time: Instant,
+    pub rate: f64,
+    pub can_control: bool,
+    pub can_raise: bool,
+    pub can_quit: bool,
+    /// `None` if the player doesn't support shuffling.
+    pub shuffle: Option<bool>,
+    /// `None` if the player doesn't support looping.
+    pub loop_status: Option<LoopStatus>,
+    /// `None` if the player doesn't have its own volume.
+    pub volume: Option<f64>,
 }
 
 impl PlayerStatus {
-    async fn new(player: Player) -> Option<Self> {
+    async fn new(mpris_player: &MprisPlayer) -> Option<Self> {
+        let player = mpris_player.player.clone();
         let metadata = player.metadata().await.ok()?;
         let pathname = metadata.url().unwrap_or("".into());
         let pathbuf = PathBuf::from(pathname);
@@ -45,16 +76,16 @@ impl PlayerStatus {
         let artists = metadata
             .artists()
             .map(|a| a.into_iter().map(Cow::from).collect::<Vec<_>>());
-        let icon = metadata
-            .art_url()
-            .and_then(|u| url::Url::parse(&u).ok())
-            .and_then(|u| {
-                if u.scheme() == "file" {
-                    u.to_file_path().ok()
-                } else {
-                    None
-                }
-            });
+        let art_url = metadata.art_url().and_then(|u| url::Url::parse(&u).ok());
+        let icon = art_url.as_ref().and_then(|u| {
+            if u.scheme() == "file" {
+                u.to_file_path().ok()
+            } else if ArtCache::supports(u) {
+                ArtCache::default().cached(u)
+            } else {
+                None
+            }
+        });
 
         let (playback_status, can_pause, can_play, can_go_previous, can_go_next) = join!(
             player.playback_status(),
@@ -63,8 +94,26 @@ impl PlayerStatus {
             player.can_go_previous(),
             player.can_go_next()
         );
+        let (identity, desktop_entry) = join!(
+            mpris_player.media_player.identity(),
+            mpris_player.media_player.desktop_entry()
+        );
+        let (can_seek, position, rate) = join!(player.can_seek(), player.position(), player.rate());
+        let (can_control, shuffle, loop_status, volume) = join!(
+            player.can_control(),
+            player.shuffle(),
+            player.loop_status(),
+            player.volume()
+        );
+        let (can_raise, can_quit) = join!(
+            mpris_player.media_player.can_raise(),
+            mpris_player.media_player.can_quit()
+        );
         Some(Self {
+            identity: identity.ok(),
+            desktop_entry: desktop_entry.ok(),
             icon,
+            art_url,
             title,
             artists,
             status: playback_status.unwrap_or(PlaybackStatus::Stopped),
@@ -72,9 +121,41 @@ impl PlayerStatus {
             can_play: can_play.unwrap_or_default(),
             can_go_previous: can_go_previous.unwrap_or_default(),
             can_go_next: can_go_next.unwrap_or_default(),
+            can_seek: can_seek.unwrap_or_default(),
+            track_id: metadata.trackid(),
+            length: metadata.length(),
+            position: position.ok(),
+            position_time: Instant::now(),
+            rate: rate.unwrap_or(1.0),
+            can_control: can_control.unwrap_or_default(),
+            can_raise: can_raise.unwrap_or_default(),
+            can_quit: can_quit.unwrap_or_default(),
+            shuffle: shuffle.ok(),
+            loop_status: loop_status.ok(),
+            volume: volume.ok(),
+            media_player: mpris_player.media_player.clone(),
             player,
         })
     }
+
+    /// Playback position at `now`, extrapolated from the last read position and the playback
+    /// rate, so it doesn't have to be polled.
+    pub fn position_at(&self, now: Instant) -> Option<Duration> {
+        let mut position = self.position?;
+        if matches!(self.status, PlaybackStatus::Playing) && self.rate > 0.0 {
+            position += now
+                .saturating_duration_since(self.position_time)
+                .mul_f64(self.rate);
+        }
+        Some(match self.length {
+            Some(length) => position.min(length),
+            None => position,
+        })
+    }
+
+    pub fn name(&self) -> &BusName {
+        self.player.destination()
+    }
 }
 
 pub fn mpris_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
@@ -90,7 +171,6 @@ pub fn mpris_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
 #[derive(Clone, Debug)]
 struct MprisPlayer {
     player: Player,
-    #[allow(dead_code)]
     media_player: MediaPlayer,
 }
 
@@ -109,8 +189,8 @@ impl MprisPlayer {
 
 #[derive(Clone, Debug)]
 pub enum MprisUpdate {
-    Setup,
-    Player(PlayerStatus),
+    /// Status of every player, sorted by bus name.
+    Players(Vec<PlayerStatus>),
     Finished,
 }
 
@@ -120,6 +200,24 @@ pub enum MprisRequest {
     Pause,
     Next,
     Previous,
+    SetPosition(Duration),
+    SetShuffle(bool),
+    SetLoopStatus(LoopStatus),
+    SetVolume(f64),
+    Raise,
+    Quit,
+}
+
+type PlayerChangedStream = Box<dyn futures::Stream<Item = OwnedBusName> + Unpin + Send>;
+
+/// Tags every item of a player's property stream with the player's bus name, so only that
+/// player's status has to be read again.
+fn tagged<S>(name: &OwnedBusName, stream: S) -> PlayerChangedStream
+where
+    S: futures::Stream + Unpin + Send + 'static,
+{
+    let name = name.clone();
+    Box::new(stream.map(move |_| name.clone()))
 }
 
 struct State {
@@ -127,9 +225,9 @@ struct State {
     enumerator_stream:
         Box<dyn futures::Stream<Item = zbus::Result<enumerator::Event>> + Unpin + Send>,
     players: Vec<MprisPlayer>,
-    active_player: Option<MprisPlayer>,
-    active_player_metadata_stream: Option<Box<dyn futures::Stream<Item = ()> + Unpin + Send>>,
-    any_player_state_stream: futures::stream::SelectAll<zbus::PropertyStream<'static, String>>,
+    /// Last read status of each player, sorted by bus name like `players`.
+    statuses: Vec<PlayerStatus>,
+    any_player_changed_stream: futures::stream::SelectAll<PlayerChangedStream>,
 }
 
 impl State {
@@ -155,16 +253,23 @@ impl State {
         // pre-sort by path so that the same player is always selected
         players.sort_by(|a, b| a.name().cmp(&b.name()));
 
+        let mut statuses = Vec::with_capacity(players.len());
+        for player in &players {
+            if let Some(player_status) = PlayerStatus::new(player).await {
+                statuses.push(player_status);
+            } else {
+                tracing::error!("Failed to get player status.");
+            }
+        }
+
         let mut state = Self {
             conn,
             enumerator_stream: Box::new(enumerator_stream),
             players,
-            active_player: None,
-            active_player_metadata_stream: None,
-            any_player_state_stream: futures::stream::select_all(Vec::new()),
+            statuses,
+            any_player_changed_stream: futures::stream::select_all(Vec::new()),
         };
-        state.update_active_player().await;
-        state.update_any_player_state_stream().await;
+        state.update_any_player_changed_stream().await;
         Ok(state)
     }
 
@@ -178,45 +283,65 @@ impl State {
         };
         self.players.push(player);
         self.players.sort_by(|a, b| a.name().cmp(&b.name()));
-        self.update_any_player_state_stream().await;
+        self.update_any_player_changed_stream().await;
     }
 
     async fn remove_player(&mut self, name: OwnedBusName) {
         if let Some(idx) = self.players.iter().position(|p| p.name() == &name) {
             self.players.remove(idx);
         }
-        self.update_any_player_state_stream().await;
+        self.statuses.retain(|s| s.name() != &name);
+        self.update_any_player_changed_stream().await;
     }
 
-    async fn update_active_player(&mut self) {
-        let new_active_player = find_active(&self.players).await;
-        if self.active_player.as_ref().map(|p| p.name()) != new_active_player.map(|p| p.name()) {
-            self.active_player = new_active_player.cloned();
-            if let Some(player) = new_active_player {
-                let controls_changed = futures::stream::select_all([
-                    player.player.receive_can_pause_changed().await,
-                    player.player.receive_can_play_changed().await,
-                    player.player.receive_can_go_previous_changed().await,
-                    player.player.receive_can_go_next_changed().await,
-                ]);
-                let metadata_changed = player.player.receive_metadata_changed().await;
-                let stream = futures::stream::select(
-                    controls_changed.map(|_| ()),
-                    metadata_changed.map(|_| ()),
-                );
-                self.active_player_metadata_stream = Some(Box::new(stream));
-            } else {
-                self.active_player_metadata_stream = None;
+    /// Reads the status of the player `name` again, leaving the other players' statuses as they
+    /// were.
+    async fn refresh_player(&mut self, name: &OwnedBusName) {
+        let Some(player) = self.players.iter().find(|p| p.name() == name) else {
+            return;
+        };
+        let Some(player_status) = PlayerStatus::new(player).await else {
+            tracing::error!("Failed to get player status.");
+            return;
+        };
+        match self.statuses.iter().position(|s| s.name() == name) {
+            Some(idx) => self.statuses[idx] = player_status,
+            None => {
+                self.statuses.push(player_status);
+                self.statuses.sort_by(|a, b| a.name().cmp(&b.name()));
             }
         }
     }
 
-    async fn update_any_player_state_stream(&mut self) {
-        let mut listeners = Vec::with_capacity(self.players.len());
+    async fn update_any_player_changed_stream(&mut self) {
+        let mut listeners: Vec<PlayerChangedStream> = Vec::with_capacity(self.players.len() * 12);
         for p in &self.players {
-            listeners.push(p.player.receive_playback_status_changed().await);
+            let player = &p.player;
+            let name = OwnedBusName::from(p.name().to_owned());
+            listeners.push(tagged(
+                &name,
+                player.receive_playback_status_changed().await,
+            ));
+            listeners.push(tagged(&name, player.receive_metadata_changed().await));
+            listeners.push(tagged(&name, player.receive_can_pause_changed().await));
+            listeners.push(tagged(&name, player.receive_can_play_changed().await));
+            listeners.push(tagged(
+                &name,
+                player.receive_can_go_previous_changed().await,
+            ));
+            listeners.push(tagged(&name, player.receive_can_go_next_changed().await));
+            listeners.push(tagged(&name, player.receive_can_seek_changed().await));
+            listeners.push(tagged(&name, player.receive_rate_changed().await));
+            listeners.push(tagged(&name, player.receive_shuffle_changed().await));
+            listeners.push(tagged(&name, player.receive_loop_status_changed().await));
+            listeners.push(tagged(&name, player.receive_volume_changed().await));
+            // Position isn't announced as it changes, so players emit `Seeked` on jumps
+            match player.receive_seeked().await {
+                Ok(seeked) => listeners.push(tagged(&name, seeked)),
+                Err(err) => tracing::error!("Failed to listen for seeks: {}", err),
+            }
         }
-        self.any_player_state_stream = futures::stream::select_all(listeners);
+        self.any_player_changed_stream = futures::stream::select_all(listeners);
     }
 }
 
@@ -230,28 +355,17 @@ async fn run(output: &mut futures::channel::mpsc::Sender<MprisUpdate>) {
     };
 
     loop {
-        if let Some(player) = &state.active_player {
-            if let Some(player_status) = PlayerStatus::new(player.player.clone()).await {
-                _ = output.send(MprisUpdate::Player(player_status)).await;
-            } else {
-                tracing::error!("Failed to get player status.");
-            };
-        } else {
-            let _ = output.send(MprisUpdate::Setup).await;
-        }
+        _ = output
+            .send(MprisUpdate::Players(state.statuses.clone()))
+            .await;
 
-        let metadata_changed_next = OptionFuture::from(
-            state
-                .active_player_metadata_stream
-                .as_mut()
-                .map(|s| s.next()),
-        );
         tokio::select! {
-            _ = metadata_changed_next, if state.active_player.is_some() => {
-            },
             event = state.enumerator_stream.next() => {
                 match event {
-                    Some(Ok(enumerator::Event::Add(name))) => state.add_player(name).await,
+                    Some(Ok(enumerator::Event::Add(name))) => {
+                        state.add_player(name.clone()).await;
+                        state.refresh_player(&name).await;
+                    }
                     Some(Ok(enumerator::Event::Remove(name))) => state.remove_player(name).await,
                     Some(Err(err)) => {
                         tracing::error!("Error listening for mpris clients: {:?}", err);
@@ -259,33 +373,24 @@ async fn run(output: &mut futures::channel::mpsc::Sender<MprisUpdate>) {
                     }
                     None => {}
                 }
-                state.update_active_player().await;
             }
-            _ = state.any_player_state_stream.next(), if !state.players.is_empty() => {
-                state.update_active_player().await;
-            },
+            Some(name) = state.any_player_changed_stream.next(), if !state.players.is_empty() => {
+                state.refresh_player(&name).await;
+            }
         };
     }
 }
 
-async fn find_active<'a>(players: &'a Vec<MprisPlayer>) -> Option<&'a MprisPlayer> {
-    let mut best = (0, None::<&'a MprisPlayer>);
-    let eval = |p: Player| async move {
-        let v = {
-            let status = p.playback_status().await;
-
-            match status {
-                Ok(mpris2_zbus::player::PlaybackStatus::Playing) => 100,
-                Ok(mpris2_zbus::player::PlaybackStatus::Paused) => 10,
-                _ => return 0,
-            }
+/// The player to control when the user hasn't picked one. Playing players are preferred over
+/// paused ones, and stopped players are never picked.
+pub fn find_active(players: &[PlayerStatus]) -> Option<&PlayerStatus> {
+    let mut best = (0, None);
+    for p in players {
+        let v = match p.status {
+            PlaybackStatus::Playing => 2,
+            PlaybackStatus::Paused => 1,
+            PlaybackStatus::Stopped => 0,
         };
-
-        v + p.metadata().await.is_ok() as i32
-    };
-
-    for p in players.iter() {
-        let v = eval(p.player.clone()).await;
         if v > best.0 {
             best = (v, Some(p));
         }
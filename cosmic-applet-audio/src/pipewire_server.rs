@@ -0,0 +1,1123 @@
+//! An audio server built on the native PipeWire client API, used instead of `PulseServer` when
+//! the `pipewire` feature is enabled.
+//!
+//! Every audio node, device, and link is bound as soon as it appears in the registry, and its
+//! properties and params are cached as PipeWire sends them. Requests from the GUI are answered
+//! from that cache, so nothing waits on the server once it is connected.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io::Cursor,
+    os::fd::AsRawFd,
+    rc::{Rc, Weak},
+    time::{Duration, Instant},
+};
+
+use libpulse_binding::{
+    channelmap::{Map, Position},
+    volume::{ChannelVolumes, Volume},
+};
+use pipewire as pw;
+use pw::{
+    device::{Device, DeviceListener},
+    metadata::{Metadata, MetadataListener},
+    node::{Node, NodeInfoRef, NodeListener, NodeState},
+    registry::{GlobalObject, Registry},
+    spa::{
+        param::ParamType,
+        pod::{
+            deserialize::PodDeserializer, serialize::PodSerializer, Object, Pod, Property, Value,
+            ValueArray,
+        },
+        sys as spa_sys,
+        utils::{dict::DictRef, Direction, Id, SpaTypes},
+    },
+    types::ObjectType,
+};
+use tokio::io::Interest;
+
+use crate::pulse::{
+    AudioServer, CardInfo, CardProfile, Changes, DeviceInfo, PollFds, PortInfo, StreamInfo,
+};
+
+/// How long connecting waits for the server to send the initial objects.
+const ROUNDTRIP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Index of a stream that isn't linked to any device, like `PA_INVALID_INDEX`.
+const INVALID_INDEX: u32 = u32::MAX;
+
+#[derive(Debug)]
+pub enum PipeWireServerError {
+    PipeWire(pw::Error),
+    Timeout,
+    Disconnected,
+    NoDevice,
+}
+
+impl From<pw::Error> for PipeWireServerError {
+    fn from(err: pw::Error) -> Self {
+        Self::PipeWire(err)
+    }
+}
+
+pub struct PipeWireServer {
+    // Fields are dropped in order, so the proxies and listeners go before the connection
+    state: Rc<RefCell<State>>,
+    _registry_listener: pw::registry::Listener,
+    _core_listener: pw::core::Listener,
+    registry: Rc<Registry>,
+    core: pw::core::Core,
+    _context: pw::context::Context,
+    mainloop: pw::main_loop::MainLoop,
+}
+
+// The part of the graph the applet shows, kept up to date by the listeners
+#[derive(Default)]
+struct State {
+    nodes: HashMap<u32, NodeEntry>,
+    devices: HashMap<u32, DeviceEntry>,
+    /// Output and input node of each link.
+    links: HashMap<u32, (u32, u32)>,
+    metadata: Option<(Metadata, MetadataListener)>,
+    default_sink: Option<String>,
+    default_source: Option<String>,
+    changes: Changes,
+    error: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Sink,
+    Source,
+    SinkInput,
+    SourceOutput,
+}
+
+impl NodeKind {
+    fn from_media_class(media_class: &str) -> Option<Self> {
+        match media_class {
+            "Audio/Sink" => Some(Self::Sink),
+            "Audio/Source" | "Audio/Source/Virtual" => Some(Self::Source),
+            "Stream/Output/Audio" => Some(Self::SinkInput),
+            "Stream/Input/Audio" => Some(Self::SourceOutput),
+            _ => None,
+        }
+    }
+
+    fn mark_changed(self, changes: &mut Changes) {
+        match self {
+            Self::Sink => changes.sinks = true,
+            Self::Source => changes.sources = true,
+            Self::SinkInput => changes.sink_inputs = true,
+            Self::SourceOutput => changes.source_outputs = true,
+        }
+    }
+}
+
+struct NodeEntry {
+    proxy: Node,
+    _listener: NodeListener,
+    kind: NodeKind,
+    props: HashMap<String, String>,
+    running: bool,
+    volume: ChannelVolumes,
+    channel_map: Map,
+    mute: bool,
+}
+
+impl NodeEntry {
+    fn prop(&self, key: &str) -> Option<String> {
+        self.props.get(key).cloned()
+    }
+
+    /// Card the node belongs to, and its device index within the card's profile.
+    fn card_device(&self) -> Option<(u32, i32)> {
+        let card = self.props.get(*pw::keys::DEVICE_ID)?.parse().ok()?;
+        let device = self.props.get("card.profile.device")?.parse().ok()?;
+        Some((card, device))
+    }
+}
+
+struct DeviceEntry {
+    proxy: Device,
+    _listener: DeviceListener,
+    props: HashMap<String, String>,
+    profiles: Vec<Profile>,
+    active_profile: Option<i32>,
+    routes: Vec<Route>,
+    active_routes: Vec<Route>,
+}
+
+#[derive(Debug, Clone)]
+struct Profile {
+    index: i32,
+    name: String,
+    description: Option<String>,
+    available: bool,
+}
+
+impl Profile {
+    fn parse(object: &Object) -> Option<Self> {
+        let mut index = None;
+        let mut name = None;
+        let mut description = None;
+        let mut available = true;
+        for property in &object.properties {
+            match (property.key, &property.value) {
+                (spa_sys::SPA_PARAM_PROFILE_index, Value::Int(value)) => index = Some(*value),
+                (spa_sys::SPA_PARAM_PROFILE_name, Value::String(value)) => {
+                    name = Some(value.clone())
+                }
+                (spa_sys::SPA_PARAM_PROFILE_description, Value::String(value)) => {
+                    description = Some(value.clone())
+                }
+                (spa_sys::SPA_PARAM_PROFILE_available, Value::Id(Id(value))) => {
+                    available = *value != spa_sys::SPA_PARAM_AVAILABILITY_no
+                }
+                _ => {}
+            }
+        }
+        Some(Self {
+            index: index?,
+            name: name?,
+            description,
+            available,
+        })
+    }
+}
+
+/// A port of a card, such as headphones or speakers.
+#[derive(Debug, Clone)]
+struct Route {
+    index: i32,
+    direction: Direction,
+    /// Profile device the route is active on. Only set for active routes.
+    device: i32,
+    /// Profile devices the route can be used with. Only set for available routes.
+    devices: Vec<i32>,
+    name: String,
+    description: Option<String>,
+    available: bool,
+}
+
+impl Route {
+    fn parse(object: &Object) -> Option<Self> {
+        let mut index = None;
+        let mut direction = None;
+        let mut device = -1;
+        let mut devices = Vec::new();
+        let mut name = None;
+        let mut description = None;
+        let mut available = true;
+        for property in &object.properties {
+            match (property.key, &property.value) {
+                (spa_sys::SPA_PARAM_ROUTE_index, Value::Int(value)) => index = Some(*value),
+                (spa_sys::SPA_PARAM_ROUTE_direction, Value::Id(Id(value))) => {
+                    direction = Some(Direction::from_raw(*value))
+                }
+                (spa_sys::SPA_PARAM_ROUTE_device, Value::Int(value)) => device = *value,
+                (spa_sys::SPA_PARAM_ROUTE_devices, Value::ValueArray(ValueArray::Int(value))) => {
+                    devices = value.clone()
+                }
+                (spa_sys::SPA_PARAM_ROUTE_name, Value::String(value)) => name = Some(value.clone()),
+                (spa_sys::SPA_PARAM_ROUTE_description, Value::String(value)) => {
+                    description = Some(value.clone())
+                }
+                (spa_sys::SPA_PARAM_ROUTE_available, Value::Id(Id(value))) => {
+                    available = *value != spa_sys::SPA_PARAM_AVAILABILITY_no
+                }
+                _ => {}
+            }
+        }
+        Some(Self {
+            index: index?,
+            direction: direction?,
+            device,
+            devices,
+            name: name?,
+            description,
+            available,
+        })
+    }
+}
+
+impl State {
+    fn node_id(&self, kind: NodeKind, name: &str) -> Option<u32> {
+        self.nodes
+            .iter()
+            .find(|(_, node)| {
+                node.kind == kind
+                    && node.props.get(*pw::keys::NODE_NAME).map(String::as_str) == Some(name)
+            })
+            .map(|(id, _)| *id)
+    }
+
+    // The route a sink or source is currently using, with the card it belongs to
+    fn active_route(&self, node: &NodeEntry) -> Option<(&DeviceEntry, &Route)> {
+        let (card, device) = node.card_device()?;
+        let card = self.devices.get(&card)?;
+        let route = card.active_routes.iter().find(|r| r.device == device)?;
+        Some((card, route))
+    }
+
+    // Device a stream is linked to
+    fn stream_device(&self, id: u32, kind: NodeKind) -> u32 {
+        self.links
+            .values()
+            .find_map(|(output, input)| match kind {
+                NodeKind::SinkInput if *output == id => Some(*input),
+                NodeKind::SourceOutput if *input == id => Some(*output),
+                _ => None,
+            })
+            .unwrap_or(INVALID_INDEX)
+    }
+
+    fn device_info(&self, id: u32, node: &NodeEntry) -> DeviceInfo {
+        let direction = if node.kind == NodeKind::Sink {
+            Direction::Output
+        } else {
+            Direction::Input
+        };
+        let (ports, active_port) = match node
+            .card_device()
+            .and_then(|(card, device)| Some((self.devices.get(&card)?, device)))
+        {
+            Some((card, device)) => (
+                card.routes
+                    .iter()
+                    .filter(|r| r.direction == direction && r.devices.contains(&device))
+                    .map(|r| PortInfo {
+                        name: r.name.clone(),
+                        description: r.description.clone(),
+                        available: r.available,
+                    })
+                    .collect(),
+                card.active_routes
+                    .iter()
+                    .find(|r| r.device == device)
+                    .map(|r| r.name.clone()),
+            ),
+            None => (Vec::new(), None),
+        };
+
+        DeviceInfo {
+            name: node.prop(*pw::keys::NODE_NAME),
+            description: node.prop(*pw::keys::NODE_DESCRIPTION),
+            volume: node.volume,
+            channel_map: node.channel_map,
+            mute: node.mute,
+            index: id,
+            card: node.card_device().map(|(card, _)| card),
+            ports,
+            active_port,
+            monitor_of_sink: None,
+        }
+    }
+
+    fn stream_info(&self, id: u32, node: &NodeEntry) -> StreamInfo {
+        let device = self.stream_device(id, node.kind);
+        StreamInfo {
+            index: id,
+            device,
+            name: node.prop(*pw::keys::MEDIA_NAME),
+            application: node.prop(*pw::keys::APP_NAME),
+            icon_name: node.prop(*pw::keys::APP_ICON_NAME),
+            volume: node.volume,
+            mute: node.mute,
+            corked: !node.running,
+            // Recording from a sink captures what it plays back
+            monitor: node.kind == NodeKind::SourceOutput
+                && self
+                    .nodes
+                    .get(&device)
+                    .is_some_and(|device| device.kind == NodeKind::Sink),
+        }
+    }
+
+    fn devices(&self, kind: NodeKind) -> Vec<DeviceInfo> {
+        let mut devices: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.kind == kind)
+            .map(|(id, node)| self.device_info(*id, node))
+            .collect();
+        devices.sort_by_key(|device| device.index);
+        devices
+    }
+
+    fn streams(&self, kind: NodeKind) -> Vec<StreamInfo> {
+        let mut streams: Vec<_> = self
+            .nodes
+            .iter()
+            // Skip peak detection streams from mixers like pavucontrol
+            .filter(|(_, node)| {
+                node.kind == kind
+                    && node
+                        .props
+                        .get(*pw::keys::STREAM_MONITOR)
+                        .map(String::as_str)
+                        != Some("true")
+            })
+            .map(|(id, node)| self.stream_info(*id, node))
+            .collect();
+        streams.sort_by_key(|stream| stream.index);
+        streams
+    }
+
+    fn default_device(&self, kind: NodeKind) -> Option<DeviceInfo> {
+        let name = match kind {
+            NodeKind::Sink => self.default_sink.as_ref(),
+            _ => self.default_source.as_ref(),
+        }?;
+        let id = self.node_id(kind, name)?;
+        Some(self.device_info(id, &self.nodes[&id]))
+    }
+
+    fn node_info(&mut self, id: u32, info: &NodeInfoRef) {
+        let Some(node) = self.nodes.get_mut(&id) else {
+            return;
+        };
+        if let Some(props) = info.props() {
+            node.props = dict_to_map(props);
+        }
+        node.running = matches!(info.state(), NodeState::Running);
+        node.kind.mark_changed(&mut self.changes);
+    }
+
+    fn node_props(&mut self, id: u32, pod: &Pod) {
+        let (Some(node), Some(object)) = (self.nodes.get_mut(&id), parse_object(pod)) else {
+            return;
+        };
+        for property in &object.properties {
+            match (property.key, &property.value) {
+                (spa_sys::SPA_PROP_mute, Value::Bool(mute)) => node.mute = *mute,
+                (
+                    spa_sys::SPA_PROP_channelVolumes,
+                    Value::ValueArray(ValueArray::Float(volumes)),
+                ) => {
+                    node.volume.set_len(volumes.len() as u8);
+                    for (volume, linear) in node.volume.get_mut().iter_mut().zip(volumes) {
+                        *volume = volume_from_linear(*linear);
+                    }
+                }
+                (spa_sys::SPA_PROP_channelMap, Value::ValueArray(ValueArray::Id(channels))) => {
+                    node.channel_map.set_len(channels.len() as u8);
+                    for (position, Id(channel)) in
+                        node.channel_map.get_mut().iter_mut().zip(channels)
+                    {
+                        *position = channel_position(*channel);
+                    }
+                }
+                _ => {}
+            }
+        }
+        node.kind.mark_changed(&mut self.changes);
+    }
+
+    fn device_param(&mut self, id: u32, param_type: ParamType, pod: &Pod) {
+        let (Some(device), Some(object)) = (self.devices.get_mut(&id), parse_object(pod)) else {
+            return;
+        };
+        if param_type == ParamType::EnumProfile {
+            if let Some(profile) = Profile::parse(&object) {
+                device.profiles.retain(|p| p.index != profile.index);
+                device.profiles.push(profile);
+                device.profiles.sort_by_key(|p| p.index);
+            }
+        } else if param_type == ParamType::Profile {
+            device.active_profile = Profile::parse(&object).map(|p| p.index);
+        } else if param_type == ParamType::EnumRoute {
+            if let Some(route) = Route::parse(&object) {
+                device.routes.retain(|r| r.index != route.index);
+                device.routes.push(route);
+                device.routes.sort_by_key(|r| r.index);
+            }
+        } else if param_type == ParamType::Route {
+            if let Some(route) = Route::parse(&object) {
+                device.active_routes.retain(|r| r.device != route.device);
+                device.active_routes.push(route);
+            }
+        }
+        // Ports are shown on the sinks and sources of the card
+        self.changes.cards = true;
+        self.changes.sinks = true;
+        self.changes.sources = true;
+    }
+
+    fn metadata_property(&mut self, subject: u32, key: Option<&str>, value: Option<&str>) {
+        if subject != pw::core::PW_ID_CORE {
+            return;
+        }
+        match key {
+            Some("default.audio.sink") => {
+                self.default_sink = value.and_then(metadata_name);
+                self.changes.sinks = true;
+            }
+            Some("default.audio.source") => {
+                self.default_source = value.and_then(metadata_name);
+                self.changes.sources = true;
+            }
+            // All properties were removed
+            None => {
+                self.default_sink = None;
+                self.default_source = None;
+                self.changes.sinks = true;
+                self.changes.sources = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn remove_global(&mut self, id: u32) {
+        if let Some(node) = self.nodes.remove(&id) {
+            node.kind.mark_changed(&mut self.changes);
+            match node.kind {
+                NodeKind::Sink => self.changes.removed_sinks.push(id),
+                NodeKind::Source => self.changes.removed_sources.push(id),
+                _ => {}
+            }
+        } else if self.devices.remove(&id).is_some() {
+            self.changes.cards = true;
+        } else if self.links.remove(&id).is_some() {
+            self.changes.sink_inputs = true;
+            self.changes.source_outputs = true;
+        }
+    }
+}
+
+// Bind the objects the applet is interested in, and listen for their changes
+fn add_global(state_rc: &Rc<RefCell<State>>, registry: &Registry, global: &GlobalObject<&DictRef>) {
+    let props = global.props.map(dict_to_map).unwrap_or_default();
+    let id = global.id;
+    let weak = Rc::downgrade(state_rc);
+
+    match global.type_ {
+        ObjectType::Node => {
+            let Some(kind) = props
+                .get(*pw::keys::MEDIA_CLASS)
+                .and_then(|class| NodeKind::from_media_class(class))
+            else {
+                return;
+            };
+            let proxy: Node = match registry.bind(global) {
+                Ok(proxy) => proxy,
+                Err(err) => {
+                    tracing::error!("Failed to bind node {}: {}", id, err);
+                    return;
+                }
+            };
+            let listener = proxy
+                .add_listener_local()
+                .info({
+                    let state = weak.clone();
+                    move |info| with_state(&state, |state| state.node_info(id, info))
+                })
+                .param(move |_seq, param_type, _index, _next, pod| {
+                    if let (ParamType::Props, Some(pod)) = (param_type, pod) {
+                        with_state(&weak, |state| state.node_props(id, pod));
+                    }
+                })
+                .register();
+            proxy.subscribe_params(&[ParamType::Props]);
+
+            let mut state = state_rc.borrow_mut();
+            kind.mark_changed(&mut state.changes);
+            match kind {
+                NodeKind::Sink => state.changes.added_sinks.push(id),
+                NodeKind::Source => state.changes.added_sources.push(id),
+                _ => {}
+            }
+            state.nodes.insert(
+                id,
+                NodeEntry {
+                    proxy,
+                    _listener: listener,
+                    kind,
+                    props,
+                    running: false,
+                    volume: ChannelVolumes::default(),
+                    channel_map: Map::default(),
+                    mute: false,
+                },
+            );
+        }
+        ObjectType::Device => {
+            if props.get(*pw::keys::MEDIA_CLASS).map(String::as_str) != Some("Audio/Device") {
+                return;
+            }
+            let proxy: Device = match registry.bind(global) {
+                Ok(proxy) => proxy,
+                Err(err) => {
+                    tracing::error!("Failed to bind device {}: {}", id, err);
+                    return;
+                }
+            };
+            let listener = proxy
+                .add_listener_local()
+                .info({
+                    let state = weak.clone();
+                    move |info| {
+                        with_state(&state, |state| {
+                            if let (Some(device), Some(props)) =
+                                (state.devices.get_mut(&id), info.props())
+                            {
+                                device.props = dict_to_map(props);
+                                state.changes.cards = true;
+                            }
+                        })
+                    }
+                })
+                .param(move |_seq, param_type, _index, _next, pod| {
+                    if let Some(pod) = pod {
+                        with_state(&weak, |state| state.device_param(id, param_type, pod));
+                    }
+                })
+                .register();
+            proxy.subscribe_params(&[
+                ParamType::EnumProfile,
+                ParamType::Profile,
+                ParamType::EnumRoute,
+                ParamType::Route,
+            ]);
+
+            let mut state = state_rc.borrow_mut();
+            state.changes.cards = true;
+            state.devices.insert(
+                id,
+                DeviceEntry {
+                    proxy,
+                    _listener: listener,
+                    props,
+                    profiles: Vec::new(),
+                    active_profile: None,
+                    routes: Vec::new(),
+                    active_routes: Vec::new(),
+                },
+            );
+        }
+        ObjectType::Link => {
+            let node = |key: &str| props.get(key).and_then(|id| id.parse::<u32>().ok());
+            let (Some(output), Some(input)) = (
+                node(*pw::keys::LINK_OUTPUT_NODE),
+                node(*pw::keys::LINK_INPUT_NODE),
+            ) else {
+                return;
+            };
+            let mut state = state_rc.borrow_mut();
+            state.links.insert(id, (output, input));
+            state.changes.sink_inputs = true;
+            state.changes.source_outputs = true;
+        }
+        ObjectType::Metadata => {
+            // The default devices are kept in the "default" metadata
+            if props.get("metadata.name").map(String::as_str) != Some("default") {
+                return;
+            }
+            let proxy: Metadata = match registry.bind(global) {
+                Ok(proxy) => proxy,
+                Err(err) => {
+                    tracing::error!("Failed to bind metadata {}: {}", id, err);
+                    return;
+                }
+            };
+            let listener = proxy
+                .add_listener_local()
+                .property(move |subject, key, _type, value| {
+                    with_state(&weak, |state| state.metadata_property(subject, key, value));
+                    0
+                })
+                .register();
+            state_rc.borrow_mut().metadata = Some((proxy, listener));
+        }
+        _ => {}
+    }
+}
+
+fn with_state(state: &Weak<RefCell<State>>, f: impl FnOnce(&mut State)) {
+    if let Some(state) = state.upgrade() {
+        f(&mut state.borrow_mut());
+    }
+}
+
+impl PipeWireServer {
+    // Wait until the server has processed every request sent so far
+    fn roundtrip(&self) -> Result<(), PipeWireServerError> {
+        let done = Rc::new(Cell::new(false));
+        let pending = self.core.sync(0)?;
+        let _listener = self
+            .core
+            .add_listener_local()
+            .done({
+                let done = done.clone();
+                move |id, seq| {
+                    if id == pw::core::PW_ID_CORE && seq == pending {
+                        done.set(true);
+                    }
+                }
+            })
+            .register();
+
+        let deadline = Instant::now() + ROUNDTRIP_TIMEOUT;
+        while !done.get() {
+            if self.state.borrow().error {
+                return Err(PipeWireServerError::Disconnected);
+            }
+            if Instant::now() > deadline {
+                return Err(PipeWireServerError::Timeout);
+            }
+            if self.mainloop.loop_().iterate(Duration::from_millis(100)) < 0 {
+                return Err(PipeWireServerError::Disconnected);
+            }
+        }
+        Ok(())
+    }
+
+    fn set_default(&self, kind: NodeKind, name: &str) -> bool {
+        let state = self.state.borrow();
+        let (Some(new), Some((metadata, _))) = (state.node_id(kind, name), state.metadata.as_ref())
+        else {
+            return false;
+        };
+        let (key, current, streams) = match kind {
+            NodeKind::Sink => (
+                "default.configured.audio.sink",
+                state.default_sink.as_ref(),
+                NodeKind::SinkInput,
+            ),
+            _ => (
+                "default.configured.audio.source",
+                state.default_source.as_ref(),
+                NodeKind::SourceOutput,
+            ),
+        };
+        let value = serde_json::json!({ "name": name }).to_string();
+        metadata.set_property(
+            pw::core::PW_ID_CORE,
+            key,
+            Some("Spa:String:JSON"),
+            Some(&value),
+        );
+
+        // Streams that follow the default move on their own, but ones pinned to the previous
+        // default are moved explicitly, like the pulse backend does
+        if let Some(current) = current.and_then(|current| state.node_id(kind, current)) {
+            for (id, _) in state.nodes.iter().filter(|(id, node)| {
+                node.kind == streams && state.stream_device(**id, streams) == current
+            }) {
+                set_target(&state, *id, new);
+            }
+        }
+        true
+    }
+
+    fn set_volume(&self, id: u32, volume: Option<&ChannelVolumes>, mute: Option<bool>) {
+        let state = self.state.borrow();
+        let Some(node) = state.nodes.get(&id) else {
+            return;
+        };
+
+        let mut properties = Vec::new();
+        if let Some(volume) = volume {
+            let volumes = volume.get().iter().map(|v| volume_to_linear(*v)).collect();
+            properties.push(Property::new(
+                spa_sys::SPA_PROP_channelVolumes,
+                Value::ValueArray(ValueArray::Float(volumes)),
+            ));
+        }
+        if let Some(mute) = mute {
+            properties.push(Property::new(spa_sys::SPA_PROP_mute, Value::Bool(mute)));
+        }
+        let props = Object {
+            type_: SpaTypes::ObjectParamProps.as_raw(),
+            id: ParamType::Props.as_raw(),
+            properties,
+        };
+
+        // Cards keep the volume of their active port, so it is restored along with the port
+        if let Some((card, route)) = state.active_route(node) {
+            let route = Object {
+                type_: SpaTypes::ObjectParamRoute.as_raw(),
+                id: ParamType::Route.as_raw(),
+                properties: vec![
+                    Property::new(spa_sys::SPA_PARAM_ROUTE_index, Value::Int(route.index)),
+                    Property::new(spa_sys::SPA_PARAM_ROUTE_device, Value::Int(route.device)),
+                    Property::new(spa_sys::SPA_PARAM_ROUTE_props, Value::Object(props)),
+                    Property::new(spa_sys::SPA_PARAM_ROUTE_save, Value::Bool(true)),
+                ],
+            };
+            with_pod(route, |pod| card.proxy.set_param(ParamType::Route, 0, pod));
+        } else {
+            with_pod(props, |pod| node.proxy.set_param(ParamType::Props, 0, pod));
+        }
+    }
+
+    fn set_port(&self, kind: NodeKind, name: &str, port: &str) {
+        let state = self.state.borrow();
+        let Some(node) = state.node_id(kind, name).map(|id| &state.nodes[&id]) else {
+            return;
+        };
+        let Some((card, device)) = node
+            .card_device()
+            .and_then(|(card, device)| Some((state.devices.get(&card)?, device)))
+        else {
+            return;
+        };
+        let Some(route) = card
+            .routes
+            .iter()
+            .find(|r| r.name == port && r.devices.contains(&device))
+        else {
+            return;
+        };
+        let route = Object {
+            type_: SpaTypes::ObjectParamRoute.as_raw(),
+            id: ParamType::Route.as_raw(),
+            properties: vec![
+                Property::new(spa_sys::SPA_PARAM_ROUTE_index, Value::Int(route.index)),
+                Property::new(spa_sys::SPA_PARAM_ROUTE_device, Value::Int(device)),
+                Property::new(spa_sys::SPA_PARAM_ROUTE_save, Value::Bool(true)),
+            ],
+        };
+        with_pod(route, |pod| card.proxy.set_param(ParamType::Route, 0, pod));
+    }
+
+    fn move_stream(&self, index: u32, kind: NodeKind, device: &str) {
+        let state = self.state.borrow();
+        if let Some(device) = state.node_id(kind, device) {
+            set_target(&state, index, device);
+        }
+    }
+}
+
+impl AudioServer for PipeWireServer {
+    type Error = PipeWireServerError;
+
+    fn connect() -> Result<Self, Self::Error> {
+        pw::init();
+        let mainloop = pw::main_loop::MainLoop::new(None)?;
+        let context = pw::context::Context::new(&mainloop)?;
+        let core = context.connect(None)?;
+        let registry = Rc::new(core.get_registry()?);
+        let state = Rc::new(RefCell::new(State::default()));
+
+        let core_listener = core
+            .add_listener_local()
+            .error({
+                let state = Rc::downgrade(&state);
+                move |id, _seq, res, message| {
+                    tracing::error!("PipeWire error on object {}: {} {}", id, res, message);
+                    if id == pw::core::PW_ID_CORE {
+                        with_state(&state, |state| state.error = true);
+                    }
+                }
+            })
+            .register();
+
+        let registry_listener = registry
+            .add_listener_local()
+            .global({
+                let state = Rc::downgrade(&state);
+                let registry = Rc::downgrade(&registry);
+                move |global| {
+                    if let (Some(state), Some(registry)) = (state.upgrade(), registry.upgrade()) {
+                        add_global(&state, &registry, global);
+                    }
+                }
+            })
+            .global_remove({
+                let state = Rc::downgrade(&state);
+                move |id| with_state(&state, |state| state.remove_global(id))
+            })
+            .register();
+
+        let server = Self {
+            state,
+            _registry_listener: registry_listener,
+            _core_listener: core_listener,
+            registry,
+            core,
+            _context: context,
+            mainloop,
+        };
+        // Once for the globals, and once more for the info and params of the bound objects
+        server.roundtrip()?;
+        server.roundtrip()?;
+        // The GUI requests the initial lists itself
+        server.state.borrow_mut().changes = Changes::default();
+        Ok(server)
+    }
+
+    fn is_connected(&mut self) -> bool {
+        !self.state.borrow().error
+    }
+
+    fn take_changes(&mut self) -> Result<Changes, Self::Error> {
+        loop {
+            match self.mainloop.loop_().iterate(Duration::ZERO) {
+                0 => break,
+                n if n < 0 => return Err(PipeWireServerError::Disconnected),
+                _ => {}
+            }
+        }
+        let mut state = self.state.borrow_mut();
+        if state.error {
+            return Err(PipeWireServerError::Disconnected);
+        }
+        Ok(std::mem::take(&mut state.changes))
+    }
+
+    fn poll_fds(&self) -> PollFds {
+        // The loop's fd is ready whenever one of its sources, timers included, is
+        PollFds {
+            fds: vec![(self.mainloop.loop_().fd().as_raw_fd(), Interest::READABLE)],
+            timeout: None,
+        }
+    }
+
+    fn get_sinks(&self) -> Result<Vec<DeviceInfo>, Self::Error> {
+        Ok(self.state.borrow().devices(NodeKind::Sink))
+    }
+
+    fn get_sources(&self) -> Result<Vec<DeviceInfo>, Self::Error> {
+        Ok(self.state.borrow().devices(NodeKind::Source))
+    }
+
+    fn get_cards(&self) -> Result<Vec<CardInfo>, Self::Error> {
+        let state = self.state.borrow();
+        let mut cards: Vec<_> = state
+            .devices
+            .iter()
+            .map(|(id, device)| CardInfo {
+                index: *id,
+                name: device.props.get(*pw::keys::DEVICE_NAME).cloned(),
+                description: device.props.get(*pw::keys::DEVICE_DESCRIPTION).cloned(),
+                profiles: device
+                    .profiles
+                    .iter()
+                    .map(|p| CardProfile {
+                        name: p.name.clone(),
+                        description: p.description.clone(),
+                        available: p.available,
+                    })
+                    .collect(),
+                active_profile: device.active_profile.and_then(|index| {
+                    device
+                        .profiles
+                        .iter()
+                        .find(|p| p.index == index)
+                        .map(|p| p.name.clone())
+                }),
+            })
+            .collect();
+        cards.sort_by_key(|card| card.index);
+        Ok(cards)
+    }
+
+    fn get_sink_input_list(&self) -> Result<Vec<StreamInfo>, Self::Error> {
+        Ok(self.state.borrow().streams(NodeKind::SinkInput))
+    }
+
+    fn get_source_output_list(&self) -> Result<Vec<StreamInfo>, Self::Error> {
+        Ok(self.state.borrow().streams(NodeKind::SourceOutput))
+    }
+
+    fn get_default_sink(&mut self) -> Result<DeviceInfo, Self::Error> {
+        self.state
+            .borrow()
+            .default_device(NodeKind::Sink)
+            .ok_or(PipeWireServerError::NoDevice)
+    }
+
+    fn get_default_source(&mut self) -> Result<DeviceInfo, Self::Error> {
+        self.state
+            .borrow()
+            .default_device(NodeKind::Source)
+            .ok_or(PipeWireServerError::NoDevice)
+    }
+
+    fn set_default_sink(&mut self, sink: &str) -> bool {
+        self.set_default(NodeKind::Sink, sink)
+    }
+
+    fn set_default_source(&mut self, source: &str) -> bool {
+        self.set_default(NodeKind::Source, source)
+    }
+
+    fn set_sink_volume_by_name(&mut self, name: &str, volume: &ChannelVolumes) {
+        if let Some(id) = self.state.borrow().node_id(NodeKind::Sink, name) {
+            self.set_volume(id, Some(volume), Some(volume.is_muted()));
+        }
+    }
+
+    fn set_source_volume_by_name(&mut self, name: &str, volume: &ChannelVolumes) {
+        if let Some(id) = self.state.borrow().node_id(NodeKind::Source, name) {
+            self.set_volume(id, Some(volume), Some(volume.is_muted()));
+        }
+    }
+
+    fn set_sink_mute_by_name(&mut self, name: &str, mute: bool) {
+        if let Some(id) = self.state.borrow().node_id(NodeKind::Sink, name) {
+            self.set_volume(id, None, Some(mute));
+        }
+    }
+
+    fn set_source_mute_by_name(&mut self, name: &str, mute: bool) {
+        if let Some(id) = self.state.borrow().node_id(NodeKind::Source, name) {
+            self.set_volume(id, None, Some(mute));
+        }
+    }
+
+    fn set_card_profile(&mut self, index: u32, profile: &str) {
+        let state = self.state.borrow();
+        let Some(card) = state.devices.get(&index) else {
+            return;
+        };
+        let Some(profile) = card.profiles.iter().find(|p| p.name == profile) else {
+            return;
+        };
+        let profile = Object {
+            type_: SpaTypes::ObjectParamProfile.as_raw(),
+            id: ParamType::Profile.as_raw(),
+            properties: vec![
+                Property::new(spa_sys::SPA_PARAM_PROFILE_index, Value::Int(profile.index)),
+                Property::new(spa_sys::SPA_PARAM_PROFILE_save, Value::Bool(true)),
+            ],
+        };
+        with_pod(profile, |pod| {
+            card.proxy.set_param(ParamType::Profile, 0, pod)
+        });
+    }
+
+    fn set_sink_port_by_name(&mut self, name: &str, port: &str) {
+        self.set_port(NodeKind::Sink, name, port);
+    }
+
+    fn set_source_port_by_name(&mut self, name: &str, port: &str) {
+        self.set_port(NodeKind::Source, name, port);
+    }
+
+    fn set_sink_input_volume(&mut self, index: u32, volume: &ChannelVolumes) {
+        self.set_volume(index, Some(volume), Some(volume.is_muted()));
+    }
+
+    fn set_source_output_volume(&mut self, index: u32, volume: &ChannelVolumes) {
+        self.set_volume(index, Some(volume), Some(volume.is_muted()));
+    }
+
+    fn set_sink_input_mute(&mut self, index: u32, mute: bool) {
+        self.set_volume(index, None, Some(mute));
+    }
+
+    fn set_source_output_mute(&mut self, index: u32, mute: bool) {
+        self.set_volume(index, None, Some(mute));
+    }
+
+    fn move_sink_input(&mut self, index: u32, sink: &str) {
+        self.move_stream(index, NodeKind::Sink, sink);
+    }
+
+    fn move_source_output(&mut self, index: u32, source: &str) {
+        self.move_stream(index, NodeKind::Source, source);
+    }
+
+    fn kill_source_output(&mut self, index: u32) {
+        if let Err(err) = self.registry.destroy_global(index).into_result() {
+            tracing::error!("Failed to destroy stream {}: {}", index, err);
+        }
+    }
+}
+
+// Link a stream to `device` by setting its target in the default metadata
+fn set_target(state: &State, stream: u32, device: u32) {
+    let (Some((metadata, _)), Some(serial)) = (
+        state.metadata.as_ref(),
+        state
+            .nodes
+            .get(&device)
+            .and_then(|node| node.props.get(*pw::keys::OBJECT_SERIAL)),
+    ) else {
+        return;
+    };
+    metadata.set_property(stream, "target.object", Some("Spa:Id"), Some(serial));
+}
+
+fn dict_to_map(dict: &DictRef) -> HashMap<String, String> {
+    dict.iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn parse_object(pod: &Pod) -> Option<Object> {
+    match PodDeserializer::deserialize_any_from(pod.as_bytes()) {
+        Ok((_, Value::Object(object))) => Some(object),
+        _ => None,
+    }
+}
+
+fn with_pod(object: Object, f: impl FnOnce(&Pod)) {
+    match PodSerializer::serialize(Cursor::new(Vec::new()), &Value::Object(object)) {
+        Ok((cursor, _)) => {
+            if let Some(pod) = Pod::from_bytes(cursor.get_ref()) {
+                f(pod);
+            }
+        }
+        Err(err) => tracing::error!("Failed to serialize pod: {:?}", err),
+    }
+}
+
+// Name in a metadata value such as `{ "name": "alsa_output.pci-0000_00_1f.3.analog-stereo" }`
+fn metadata_name(value: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(value)
+        .ok()?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+// PipeWire volumes are linear, while PulseAudio's are cubic
+fn volume_from_linear(linear: f32) -> Volume {
+    Volume((linear.max(0.).cbrt() * Volume::NORMAL.0 as f32).round() as u32)
+}
+
+fn volume_to_linear(volume: Volume) -> f32 {
+    (volume.0 as f32 / Volume::NORMAL.0 as f32).powi(3)
+}
+
+fn channel_position(channel: u32) -> Position {
+    match channel {
+        spa_sys::SPA_AUDIO_CHANNEL_MONO => Position::Mono,
+        spa_sys::SPA_AUDIO_CHANNEL_FL => Position::FrontLeft,
+        spa_sys::SPA_AUDIO_CHANNEL_FR => Position::FrontRight,
+        spa_sys::SPA_AUDIO_CHANNEL_FC => Position::FrontCenter,
+        spa_sys::SPA_AUDIO_CHANNEL_LFE => Position::Lfe,
+        spa_sys::SPA_AUDIO_CHANNEL_SL => Position::SideLeft,
+        spa_sys::SPA_AUDIO_CHANNEL_SR => Position::SideRight,
+        spa_sys::SPA_AUDIO_CHANNEL_FLC => Position::FrontLeftOfCenter,
+        spa_sys::SPA_AUDIO_CHANNEL_FRC => Position::FrontRightOfCenter,
+        spa_sys::SPA_AUDIO_CHANNEL_RC => Position::RearCenter,
+        spa_sys::SPA_AUDIO_CHANNEL_RL => Position::RearLeft,
+        spa_sys::SPA_AUDIO_CHANNEL_RR => Position::RearRight,
+        spa_sys::SPA_AUDIO_CHANNEL_TC => Position::TopCenter,
+        spa_sys::SPA_AUDIO_CHANNEL_TFL => Position::TopFrontLeft,
+        spa_sys::SPA_AUDIO_CHANNEL_TFC => Position::TopFrontCenter,
+        spa_sys::SPA_AUDIO_CHANNEL_TFR => Position::TopFrontRight,
+        spa_sys::SPA_AUDIO_CHANNEL_TRL => Position::TopRearLeft,
+        spa_sys::SPA_AUDIO_CHANNEL_TRC => Position::TopRearCenter,
+        spa_sys::SPA_AUDIO_CHANNEL_TRR => Position::TopRearRight,
+        _ => Position::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{check_server, TestServer};
+
+    #[tokio::test]
+    #[ignore = "starts a headless pipewire"]
+    async fn pipewire_server() {
+        let _pipewire = TestServer::start(false);
+        check_server::<PipeWireServer>().await;
+    }
+}
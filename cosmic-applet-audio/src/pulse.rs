@@ -1,5 +1,6 @@
-use std::cell::RefCell;
-use std::{rc::Rc, thread};
+#[cfg(not(feature = "pipewire"))]
+use std::{cell::RefCell, rc::Rc};
+use std::{os::fd::RawFd, thread, time::Duration};
 
 extern crate libpulse_binding as pulse;
 
@@ -7,19 +8,28 @@ use cosmic::iced::{self, subscription};
 use cosmic::iced_futures::futures::{self, SinkExt};
 use cosmic_time::once_cell::sync::Lazy;
 
+#[cfg(not(feature = "pipewire"))]
 use libpulse_binding::{
     callbacks::ListResult,
     context::{
-        introspect::{Introspector, SinkInfo, SourceInfo},
+        introspect::{
+            CardInfo as PulseCardInfo, Introspector, SinkInfo, SinkInputInfo, SinkPortInfo,
+            SourceInfo, SourceOutputInfo, SourcePortInfo,
+        },
+        subscribe::{Facility, InterestMaskSet, Operation},
         Context,
     },
+    def::PortAvailable,
     error::PAErr,
     mainloop::standard::{IterateResult, Mainloop},
     proplist::Proplist,
-    volume::ChannelVolumes,
 };
+use libpulse_binding::{channelmap::Map, volume::ChannelVolumes};
 
-use tokio::sync::{mpsc, Mutex};
+use tokio::{
+    io::{unix::AsyncFd, Interest},
+    sync::{mpsc, Mutex},
+};
 
 pub static FROM_PULSE: Lazy<Mutex<Option<(mpsc::Receiver<Message>, mpsc::Sender<Message>)>>> =
     Lazy::new(|| Mutex::new(None));
@@ -116,6 +126,48 @@ async fn start_listening(
                         .await;
                     State::Connected
                 }
+                Some(Message::SetCards(cards)) => {
+                    _ = output
+                        .send(Event::MessageReceived(Message::SetCards(cards)))
+                        .await;
+                    State::Connected
+                }
+                Some(Message::SinkAdded(sink)) => {
+                    _ = output
+                        .send(Event::MessageReceived(Message::SinkAdded(sink)))
+                        .await;
+                    State::Connected
+                }
+                Some(Message::SinkRemoved(index)) => {
+                    _ = output
+                        .send(Event::MessageReceived(Message::SinkRemoved(index)))
+                        .await;
+                    State::Connected
+                }
+                Some(Message::SourceAdded(source)) => {
+                    _ = output
+                        .send(Event::MessageReceived(Message::SourceAdded(source)))
+                        .await;
+                    State::Connected
+                }
+                Some(Message::SourceRemoved(index)) => {
+                    _ = output
+                        .send(Event::MessageReceived(Message::SourceRemoved(index)))
+                        .await;
+                    State::Connected
+                }
+                Some(Message::SetSinkInputs(inputs)) => {
+                    _ = output
+                        .send(Event::MessageReceived(Message::SetSinkInputs(inputs)))
+                        .await;
+                    State::Connected
+                }
+                Some(Message::SetSourceOutputs(outputs)) => {
+                    _ = output
+                        .send(Event::MessageReceived(Message::SetSourceOutputs(outputs)))
+                        .await;
+                    State::Connected
+                }
                 Some(Message::Disconnected) => {
                     _ = output.send(Event::Disconnected).await;
                     State::Connecting
@@ -164,12 +216,34 @@ pub enum Message {
     UpdateConnection,
     SetSinks(Vec<DeviceInfo>),
     SetSources(Vec<DeviceInfo>),
+    SinkAdded(DeviceInfo),
+    SinkRemoved(u32),
+    SourceAdded(DeviceInfo),
+    SourceRemoved(u32),
     GetDefaultSink,
     GetDefaultSource,
     SetDefaultSink(DeviceInfo),
     SetDefaultSource(DeviceInfo),
     SetSinkVolumeByName(String, ChannelVolumes),
     SetSourceVolumeByName(String, ChannelVolumes),
+    SetSinkMuteByName(String, bool),
+    SetSourceMuteByName(String, bool),
+    GetSinkInputs,
+    GetSourceOutputs,
+    SetSinkInputs(Vec<StreamInfo>),
+    SetSourceOutputs(Vec<StreamInfo>),
+    SetSinkInputVolume(u32, ChannelVolumes),
+    SetSourceOutputVolume(u32, ChannelVolumes),
+    SetSinkInputMute(u32, bool),
+    SetSourceOutputMute(u32, bool),
+    MoveSinkInput(u32, String),
+    MoveSourceOutput(u32, String),
+    KillSourceOutput(u32),
+    GetCards,
+    SetCards(Vec<CardInfo>),
+    SetCardProfile(u32, String),
+    SetSinkPortByName(String, String),
+    SetSourcePortByName(String, String),
 }
 
 struct PulseHandle {
@@ -198,11 +272,39 @@ impl PulseHandle {
             // this lets us put the thread to sleep, but keep hold a single
             // thread, because pulse audio's API is not multithreaded... at all
             rt.block_on(async {
-                let mut server: Option<PulseServer> = None;
+                let mut server: Option<Server> = None;
 
                 loop {
+                    // Changes made by other clients only arrive while the mainloop is
+                    // dispatched, and requests from the GUI may have dispatched some already
+                    if let Some(cur_server) = server.as_mut() {
+                        match cur_server.take_changes() {
+                            Ok(changes) => {
+                                for msg in change_messages(cur_server, changes) {
+                                    if let Err(err) = from_pulse_send.send(msg).await {
+                                        tracing::error!("ERROR! {}", err);
+                                    }
+                                }
+                            }
+                            Err(_) => {
+                                server = None;
+                                Self::send_disconnected(&from_pulse_send).await;
+                            }
+                        }
+                    }
+
+                    // Without a server there is nothing to wait on, so this never completes
+                    let poll_fds = server
+                        .as_ref()
+                        .map(AudioServer::poll_fds)
+                        .unwrap_or_default();
+                    let msg = tokio::select! {
+                        msg = to_pulse_recv.recv() => msg,
+                        _ = wait_for_changes(poll_fds) => continue,
+                    };
+
                     // This is where the we match messages from the GUI to pass to the pulse server
-                    if let Some(msg) = to_pulse_recv.recv().await {
+                    if let Some(msg) = msg {
                         match msg {
                             Message::GetDefaultSink => {
                                 let server = match server.as_mut() {
@@ -287,13 +389,147 @@ impl PulseHandle {
                                 };
                                 server.set_source_volume_by_name(&name, &channel_volumes)
                             }
+                            Message::GetSinkInputs => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                match server.get_sink_input_list() {
+                                    Ok(inputs) => {
+                                        if let Err(err) = from_pulse_send
+                                            .send(Message::SetSinkInputs(inputs))
+                                            .await
+                                        {
+                                            tracing::error!("ERROR! {}", err);
+                                        }
+                                    }
+                                    Err(_) => Self::send_disconnected(&from_pulse_send).await,
+                                }
+                            }
+                            Message::GetSourceOutputs => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                match server.get_source_output_list() {
+                                    Ok(outputs) => {
+                                        if let Err(err) = from_pulse_send
+                                            .send(Message::SetSourceOutputs(outputs))
+                                            .await
+                                        {
+                                            tracing::error!("ERROR! {}", err);
+                                        }
+                                    }
+                                    Err(_) => Self::send_disconnected(&from_pulse_send).await,
+                                }
+                            }
+                            Message::SetSinkInputVolume(index, channel_volumes) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.set_sink_input_volume(index, &channel_volumes)
+                            }
+                            Message::SetSourceOutputVolume(index, channel_volumes) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.set_source_output_volume(index, &channel_volumes)
+                            }
+                            Message::SetSinkInputMute(index, mute) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.set_sink_input_mute(index, mute)
+                            }
+                            Message::SetSourceOutputMute(index, mute) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.set_source_output_mute(index, mute)
+                            }
+                            Message::MoveSinkInput(index, sink) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.move_sink_input(index, &sink)
+                            }
+                            Message::MoveSourceOutput(index, source) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.move_source_output(index, &source)
+                            }
+                            Message::KillSourceOutput(index) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.kill_source_output(index)
+                            }
+                            Message::GetCards => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                match server.get_cards() {
+                                    Ok(cards) => {
+                                        if let Err(err) =
+                                            from_pulse_send.send(Message::SetCards(cards)).await
+                                        {
+                                            tracing::error!("ERROR! {}", err);
+                                        }
+                                    }
+                                    Err(_) => Self::send_disconnected(&from_pulse_send).await,
+                                }
+                            }
+                            Message::SetCardProfile(index, profile) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.set_card_profile(index, &profile)
+                            }
+                            Message::SetSinkPortByName(name, port) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.set_sink_port_by_name(&name, &port)
+                            }
+                            Message::SetSourcePortByName(name, port) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.set_source_port_by_name(&name, &port)
+                            }
+                            Message::SetSinkMuteByName(name, mute) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.set_sink_mute_by_name(&name, mute)
+                            }
+                            Message::SetSourceMuteByName(name, mute) => {
+                                let server = match server.as_mut() {
+                                    Some(s) => s,
+                                    None => continue,
+                                };
+                                server.set_source_mute_by_name(&name, mute)
+                            }
                             Message::UpdateConnection => {
                                 tracing::info!(
                                     "Updating Connection, server exists: {:?}",
                                     server.is_some()
                                 );
                                 if let Some(mut cur_server) = server.take() {
-                                    if cur_server.get_server_info().is_err() {
+                                    if !cur_server.is_connected() {
                                         tracing::warn!("got error, server must be disconnected...");
                                         Self::send_disconnected(&from_pulse_send).await;
                                     } else {
@@ -302,7 +538,7 @@ impl PulseHandle {
                                         Self::send_connected(&from_pulse_send).await;
                                     }
                                 } else {
-                                    match PulseServer::connect().and_then(|server| server.init()) {
+                                    match Server::connect() {
                                         Ok(new_server) => {
                                             tracing::info!("Connected to server");
                                             Self::send_connected(&from_pulse_send).await;
@@ -323,13 +559,8 @@ impl PulseHandle {
                                     Some(s) => s,
                                     None => continue,
                                 };
-                                let default_sink = match server.get_default_sink() {
-                                    Ok(sink) => sink,
-                                    Err(_) => continue,
-                                };
-                                let to_move = server.get_sink_inputs(default_sink.index);
                                 if let Some(name) = device.name.as_ref() {
-                                    if server.set_default_sink(name, to_move) {
+                                    if server.set_default_sink(name) {
                                         if let Err(err) = from_pulse_send
                                             .send(Message::SetDefaultSink(device))
                                             .await
@@ -344,13 +575,8 @@ impl PulseHandle {
                                     Some(s) => s,
                                     None => continue,
                                 };
-                                let default_source = match server.get_default_source() {
-                                    Ok(source) => source,
-                                    Err(_) => continue,
-                                };
-                                let to_move = server.get_source_outputs(default_source.index);
                                 if let Some(name) = device.name.as_ref() {
-                                    if server.set_default_source(name, to_move) {
+                                    if server.set_default_source(name) {
                                         if let Err(err) = from_pulse_send
                                             .send(Message::SetDefaultSource(device))
                                             .await
@@ -384,12 +610,227 @@ impl PulseHandle {
     }
 }
 
+/// An audio server the pulse thread can drive.
+///
+/// Servers are only used from the pulse thread, which calls `take_changes()` whenever one of the
+/// server's `poll_fds()` is ready and between requests from the GUI, so none of the calls should
+/// block for long.
+pub(crate) trait AudioServer: Sized {
+    type Error: std::fmt::Debug;
+
+    /// Connect and wait until the server is ready.
+    fn connect() -> Result<Self, Self::Error>;
+    fn is_connected(&mut self) -> bool;
+    /// Dispatch pending events, returning what other clients changed since the last call.
+    fn take_changes(&mut self) -> Result<Changes, Self::Error>;
+    /// What to wait on before calling `take_changes()` again.
+    fn poll_fds(&self) -> PollFds;
+
+    fn get_sinks(&self) -> Result<Vec<DeviceInfo>, Self::Error>;
+    fn get_sources(&self) -> Result<Vec<DeviceInfo>, Self::Error>;
+    fn get_cards(&self) -> Result<Vec<CardInfo>, Self::Error>;
+    fn get_sink_input_list(&self) -> Result<Vec<StreamInfo>, Self::Error>;
+    fn get_source_output_list(&self) -> Result<Vec<StreamInfo>, Self::Error>;
+    fn get_default_sink(&mut self) -> Result<DeviceInfo, Self::Error>;
+    fn get_default_source(&mut self) -> Result<DeviceInfo, Self::Error>;
+
+    /// Make `sink` the default, moving the streams of the previous default to it.
+    fn set_default_sink(&mut self, sink: &str) -> bool;
+    /// Make `source` the default, moving the streams of the previous default to it.
+    fn set_default_source(&mut self, source: &str) -> bool;
+    fn set_sink_volume_by_name(&mut self, name: &str, volume: &ChannelVolumes);
+    fn set_source_volume_by_name(&mut self, name: &str, volume: &ChannelVolumes);
+    fn set_sink_mute_by_name(&mut self, name: &str, mute: bool);
+    fn set_source_mute_by_name(&mut self, name: &str, mute: bool);
+    fn set_card_profile(&mut self, index: u32, profile: &str);
+    fn set_sink_port_by_name(&mut self, name: &str, port: &str);
+    fn set_source_port_by_name(&mut self, name: &str, port: &str);
+    fn set_sink_input_volume(&mut self, index: u32, volume: &ChannelVolumes);
+    fn set_source_output_volume(&mut self, index: u32, volume: &ChannelVolumes);
+    fn set_sink_input_mute(&mut self, index: u32, mute: bool);
+    fn set_source_output_mute(&mut self, index: u32, mute: bool);
+    fn move_sink_input(&mut self, index: u32, sink: &str);
+    fn move_source_output(&mut self, index: u32, source: &str);
+    /// Disconnect the stream, the application itself keeps running.
+    fn kill_source_output(&mut self, index: u32);
+}
+
+#[cfg(not(feature = "pipewire"))]
+type Server = PulseServer;
+#[cfg(feature = "pipewire")]
+type Server = crate::pipewire_server::PipeWireServer;
+
+// Messages with the refreshed lists and defaults for everything in `changes`
+fn change_messages<S: AudioServer>(server: &mut S, changes: Changes) -> Vec<Message> {
+    let mut messages = Vec::new();
+    // Sent after the refreshed lists, so they already include the new devices
+    let mut added = Vec::new();
+    if changes.sinks {
+        if let Ok(sinks) = server.get_sinks() {
+            added.extend(
+                sinks
+                    .iter()
+                    .filter(|sink| changes.added_sinks.contains(&sink.index))
+                    .cloned()
+                    .map(Message::SinkAdded),
+            );
+            messages.push(Message::SetSinks(sinks));
+        }
+        if let Ok(sink) = server.get_default_sink() {
+            messages.push(Message::SetDefaultSink(sink));
+        }
+    }
+    if changes.sources {
+        if let Ok(sources) = server.get_sources() {
+            added.extend(
+                sources
+                    .iter()
+                    .filter(|source| changes.added_sources.contains(&source.index))
+                    .cloned()
+                    .map(Message::SourceAdded),
+            );
+            messages.push(Message::SetSources(sources));
+        }
+        if let Ok(source) = server.get_default_source() {
+            messages.push(Message::SetDefaultSource(source));
+        }
+    }
+    if changes.cards {
+        if let Ok(cards) = server.get_cards() {
+            messages.push(Message::SetCards(cards));
+        }
+    }
+    if changes.sink_inputs {
+        if let Ok(inputs) = server.get_sink_input_list() {
+            messages.push(Message::SetSinkInputs(inputs));
+        }
+    }
+    if changes.source_outputs {
+        if let Ok(outputs) = server.get_source_output_list() {
+            messages.push(Message::SetSourceOutputs(outputs));
+        }
+    }
+    messages.extend(added);
+    messages.extend(changes.removed_sinks.into_iter().map(Message::SinkRemoved));
+    messages.extend(
+        changes
+            .removed_sources
+            .into_iter()
+            .map(Message::SourceRemoved),
+    );
+
+    messages
+}
+
+/// File descriptors an `AudioServer` has to dispatch once they are ready.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PollFds {
+    pub fds: Vec<(RawFd, Interest)>,
+    /// When the server has to be dispatched even if none of the `fds` is ready.
+    pub timeout: Option<Duration>,
+}
+
+// How often to check for changes if one of the server's fds can't be watched
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Wait until one of the fds in `poll` is ready or its timeout passes
+pub(crate) async fn wait_for_changes(poll: PollFds) {
+    let mut timeout = poll.timeout;
+    let mut fds = Vec::with_capacity(poll.fds.len());
+    for (fd, interest) in poll.fds {
+        match AsyncFd::with_interest(fd, interest) {
+            Ok(async_fd) => fds.push((async_fd, interest)),
+            Err(err) => {
+                tracing::error!("Failed to watch audio server fd {}: {}", fd, err);
+                timeout = Some(timeout.map_or(FALLBACK_POLL_INTERVAL, |timeout| {
+                    timeout.min(FALLBACK_POLL_INTERVAL)
+                }));
+            }
+        }
+    }
+
+    let ready = async {
+        if fds.is_empty() {
+            return futures::future::pending().await;
+        }
+        let _ = futures::future::select_all(
+            fds.iter()
+                .map(|(async_fd, interest)| Box::pin(async_fd.ready(*interest))),
+        )
+        .await;
+    };
+    match timeout {
+        Some(timeout) => {
+            let _ = tokio::time::timeout(timeout, ready).await;
+        }
+        None => ready.await,
+    }
+}
+
+#[cfg(not(feature = "pipewire"))]
 struct PulseServer {
     mainloop: Rc<RefCell<Mainloop>>,
     context: Rc<RefCell<Context>>,
     introspector: Introspector,
+    changes: Rc<RefCell<Changes>>,
+    /// What the mainloop waited on when it was last polled, recorded by `record_poll`.
+    polled: Rc<RefCell<PollFds>>,
 }
 
+// Poll function for the mainloop that never blocks, but records what the mainloop waits on, so
+// the pulse thread can wait for it along with requests from the GUI
+#[cfg(not(feature = "pipewire"))]
+extern "C" fn record_poll(
+    ufds: *mut libc::pollfd,
+    nfds: libc::c_ulong,
+    timeout: libc::c_int,
+    userdata: *mut libc::c_void,
+) -> libc::c_int {
+    // SAFETY: `userdata` is the `polled` cell of the `PulseServer` that owns the mainloop
+    let polled = unsafe { &*(userdata as *const RefCell<PollFds>) };
+    let fds = if nfds == 0 {
+        &[][..]
+    } else {
+        // SAFETY: the mainloop passes `nfds` valid entries
+        unsafe { std::slice::from_raw_parts(ufds, nfds as usize) }
+    };
+    *polled.borrow_mut() = PollFds {
+        fds: fds
+            .iter()
+            .filter(|pollfd| pollfd.fd >= 0)
+            .map(|pollfd| {
+                let interest = if pollfd.events & libc::POLLOUT == 0 {
+                    Interest::READABLE
+                } else if pollfd.events & libc::POLLIN == 0 {
+                    Interest::WRITABLE
+                } else {
+                    Interest::READABLE | Interest::WRITABLE
+                };
+                (pollfd.fd, interest)
+            })
+            .collect(),
+        // A negative timeout waits forever
+        timeout: u64::try_from(timeout).ok().map(Duration::from_millis),
+    };
+    // SAFETY: same `ufds` and `nfds` the mainloop passed in
+    unsafe { libc::poll(ufds, nfds, 0) }
+}
+
+// Device lists that need to be refreshed after a subscription event
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Changes {
+    pub sinks: bool,
+    pub sources: bool,
+    pub sink_inputs: bool,
+    pub source_outputs: bool,
+    pub cards: bool,
+    pub added_sinks: Vec<u32>,
+    pub removed_sinks: Vec<u32>,
+    pub added_sources: Vec<u32>,
+    pub removed_sources: Vec<u32>,
+}
+
+#[cfg(not(feature = "pipewire"))]
 #[derive(Clone, Debug)]
 enum PulseServerError<'a> {
     IterateErr(IterateResult),
@@ -402,9 +843,10 @@ enum PulseServerError<'a> {
 
 // `PulseServer` code is heavily inspired by Dave Patrick Caberto's pulsectl-rs (SeaDve)
 // https://crates.io/crates/pulsectl-rs
+#[cfg(not(feature = "pipewire"))]
 impl PulseServer {
-    // connect() requires init() to be run after
-    pub fn connect() -> Result<Self, PulseServerError<'static>> {
+    // new() requires init() to be run after
+    fn new() -> Result<Self, PulseServerError<'static>> {
         // TODO: fix app name, should be variable
         let mut proplist = Proplist::new().unwrap();
         proplist
@@ -430,15 +872,22 @@ impl PulseServer {
             .connect(None, pulse::context::FlagSet::NOFLAGS, None)
             .map_err(PulseServerError::PAErr)?;
 
+        let polled = Rc::new(RefCell::new(PollFds::default()));
+        mainloop
+            .borrow_mut()
+            .set_poll_func((record_poll, Rc::as_ptr(&polled) as *mut libc::c_void));
+
         Ok(Self {
             mainloop,
             context,
             introspector,
+            changes: Rc::new(RefCell::new(Changes::default())),
+            polled,
         })
     }
 
     // Wait for pulse audio connection to complete
-    pub fn init(self) -> Result<Self, PulseServerError<'static>> {
+    fn init(self) -> Result<Self, PulseServerError<'static>> {
         loop {
             match self.mainloop.borrow_mut().iterate(false) {
                 IterateResult::Success(_) => {}
@@ -463,11 +912,161 @@ impl PulseServer {
                 _ => {}
             }
         }
+        self.subscribe()?;
         Ok(self)
     }
 
+    // Track changes to cards, devices, streams, and the default devices made by any client
+    fn subscribe(&self) -> Result<(), PulseServerError<'static>> {
+        let changes = self.changes.clone();
+        self.context
+            .borrow_mut()
+            .set_subscribe_callback(Some(Box::new(move |facility, operation, index| {
+                let mut changes = changes.borrow_mut();
+                match (facility, operation) {
+                    (Some(Facility::Sink), Some(Operation::New)) => changes.added_sinks.push(index),
+                    (Some(Facility::Sink), Some(Operation::Removed)) => {
+                        changes.removed_sinks.push(index)
+                    }
+                    (Some(Facility::Source), Some(Operation::New)) => {
+                        changes.added_sources.push(index)
+                    }
+                    (Some(Facility::Source), Some(Operation::Removed)) => {
+                        changes.removed_sources.push(index)
+                    }
+                    _ => {}
+                }
+                match facility {
+                    Some(Facility::Sink) => changes.sinks = true,
+                    Some(Facility::Source) => changes.sources = true,
+                    Some(Facility::SinkInput) => changes.sink_inputs = true,
+                    Some(Facility::SourceOutput) => changes.source_outputs = true,
+                    Some(Facility::Card) => changes.cards = true,
+                    Some(Facility::Server) => {
+                        changes.sinks = true;
+                        changes.sources = true;
+                    }
+                    _ => {}
+                }
+            })));
+
+        let op = self.context.borrow_mut().subscribe(
+            InterestMaskSet::SINK
+                | InterestMaskSet::SOURCE
+                | InterestMaskSet::SINK_INPUT
+                | InterestMaskSet::SOURCE_OUTPUT
+                | InterestMaskSet::CARD
+                | InterestMaskSet::SERVER,
+            |_| {},
+        );
+        self.wait_for_result(op)
+            .map_err(|_| PulseServerError::Misc("subscribe(): failed"))
+    }
+
+    pub fn get_server_info(&mut self) -> Result<ServerInfo, PulseServerError<'static>> {
+        let info = Rc::new(RefCell::new(Some(None)));
+        let info_ref = info.clone();
+
+        let op = self.introspector.get_server_info(move |res| {
+            info_ref.borrow_mut().as_mut().unwrap().replace(res.into());
+        });
+        self.wait_for_result(op)?;
+        info.take()
+            .flatten()
+            .ok_or(PulseServerError::Misc("get_server_info(): failed"))
+    }
+
+    fn get_source_outputs(&mut self, source: u32) -> Vec<u32> {
+        let result = Rc::new(RefCell::new(Vec::new()));
+        let result_ref = Rc::new(RefCell::new(Vec::new()));
+        let op = self.introspector.get_source_output_info_list(move |list| {
+            if let ListResult::Item(item) = list {
+                if source == item.source {
+                    result.borrow_mut().push(item.index);
+                }
+            }
+        });
+        let _ = self.wait_for_result(op);
+        result_ref.replace(Vec::new())
+    }
+
+    fn get_sink_inputs(&mut self, sink: u32) -> Vec<u32> {
+        let result = Rc::new(RefCell::new(Vec::new()));
+        let result_ref = Rc::new(RefCell::new(Vec::new()));
+        let op = self.introspector.get_sink_input_info_list(move |list| {
+            if let ListResult::Item(item) = list {
+                if sink == item.sink {
+                    result.borrow_mut().push(item.index);
+                }
+            }
+        });
+        let _ = self.wait_for_result(op);
+        result_ref.replace(Vec::new())
+    }
+
+    // after building an operation such as get_devices() we need to keep polling
+    // the pulse audio server to "wait" for the operation to complete
+    fn wait_for_result<G: ?Sized>(
+        &self,
+        operation: pulse::operation::Operation<G>,
+    ) -> Result<(), PulseServerError<'static>> {
+        // TODO: make this loop async. It is already in an async context, so
+        // we could make this thread sleep while waiting for the pulse server's
+        // response.
+        loop {
+            match self.mainloop.borrow_mut().iterate(false) {
+                IterateResult::Err(e) => {
+                    return Err(PulseServerError::IterateErr(IterateResult::Err(e)))
+                }
+                IterateResult::Quit(e) => {
+                    return Err(PulseServerError::IterateErr(IterateResult::Quit(e)))
+                }
+                IterateResult::Success(_) => {}
+            }
+            match operation.get_state() {
+                pulse::operation::State::Done => return Ok(()),
+                pulse::operation::State::Running => {}
+                pulse::operation::State::Cancelled => {
+                    return Err(PulseServerError::OperationErr(
+                        pulse::operation::State::Cancelled,
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "pipewire"))]
+impl AudioServer for PulseServer {
+    type Error = PulseServerError<'static>;
+
+    fn connect() -> Result<Self, Self::Error> {
+        Self::new().and_then(|server| server.init())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.get_server_info().is_ok()
+    }
+
+    fn take_changes(&mut self) -> Result<Changes, Self::Error> {
+        let mut mainloop = self.mainloop.borrow_mut();
+        loop {
+            // Prepared without a timeout, so `record_poll` sees the mainloop's own timeout
+            mainloop.prepare(None).map_err(PulseServerError::PAErr)?;
+            mainloop.poll().map_err(PulseServerError::PAErr)?;
+            if mainloop.dispatch().map_err(PulseServerError::PAErr)? == 0 {
+                break;
+            }
+        }
+        Ok(self.changes.take())
+    }
+
+    fn poll_fds(&self) -> PollFds {
+        self.polled.borrow().clone()
+    }
+
     // Get a list of output devices
-    pub fn get_sinks(&self) -> Result<Vec<DeviceInfo>, PulseServerError> {
+    fn get_sinks(&self) -> Result<Vec<DeviceInfo>, Self::Error> {
         let list: Rc<RefCell<Option<Vec<DeviceInfo>>>> = Rc::new(RefCell::new(Some(Vec::new())));
         let list_ref = list.clone();
 
@@ -486,7 +1085,7 @@ impl PulseServer {
     }
 
     // Get a list of input devices
-    pub fn get_sources(&self) -> Result<Vec<DeviceInfo>, PulseServerError> {
+    fn get_sources(&self) -> Result<Vec<DeviceInfo>, Self::Error> {
         let list: Rc<RefCell<Option<Vec<DeviceInfo>>>> = Rc::new(RefCell::new(Some(Vec::new())));
         let list_ref = list.clone();
 
@@ -504,20 +1103,84 @@ impl PulseServer {
         })
     }
 
-    pub fn get_server_info(&mut self) -> Result<ServerInfo, PulseServerError> {
-        let info = Rc::new(RefCell::new(Some(None)));
-        let info_ref = info.clone();
+    // Get a list of sound cards and their profiles
+    fn get_cards(&self) -> Result<Vec<CardInfo>, Self::Error> {
+        let list: Rc<RefCell<Option<Vec<CardInfo>>>> = Rc::new(RefCell::new(Some(Vec::new())));
+        let list_ref = list.clone();
 
-        let op = self.introspector.get_server_info(move |res| {
-            info_ref.borrow_mut().as_mut().unwrap().replace(res.into());
-        });
-        self.wait_for_result(op)?;
-        info.take()
-            .flatten()
-            .ok_or(PulseServerError::Misc("get_server_info(): failed"))
+        let operation =
+            self.introspector
+                .get_card_info_list(move |card_list: ListResult<&PulseCardInfo>| {
+                    if let ListResult::Item(item) = card_list {
+                        list_ref.borrow_mut().as_mut().unwrap().push(item.into());
+                    }
+                });
+        self.wait_for_result(operation).and_then(|_| {
+            list.borrow_mut().take().ok_or(PulseServerError::Misc(
+                "get_cards(): failed to wait for operation",
+            ))
+        })
+    }
+
+    // Get a list of playback streams
+    fn get_sink_input_list(&self) -> Result<Vec<StreamInfo>, Self::Error> {
+        let list: Rc<RefCell<Option<Vec<StreamInfo>>>> = Rc::new(RefCell::new(Some(Vec::new())));
+        let list_ref = list.clone();
+
+        let operation = self.introspector.get_sink_input_info_list(
+            move |input_list: ListResult<&SinkInputInfo>| {
+                if let ListResult::Item(item) = input_list {
+                    list_ref.borrow_mut().as_mut().unwrap().push(item.into());
+                }
+            },
+        );
+        self.wait_for_result(operation).and_then(|_| {
+            list.borrow_mut().take().ok_or(PulseServerError::Misc(
+                "get_sink_input_list(): failed to wait for operation",
+            ))
+        })
     }
 
-    fn set_default_sink(&mut self, sink: &str, to_move: Vec<u32>) -> bool {
+    // Get a list of recording streams
+    fn get_source_output_list(&self) -> Result<Vec<StreamInfo>, Self::Error> {
+        let list: Rc<RefCell<Option<Vec<StreamInfo>>>> = Rc::new(RefCell::new(Some(Vec::new())));
+        let list_ref = list.clone();
+
+        let operation = self.introspector.get_source_output_info_list(
+            move |output_list: ListResult<&SourceOutputInfo>| {
+                if let ListResult::Item(item) = output_list {
+                    // Skip peak detection streams from mixers like pavucontrol
+                    if item.resample_method.as_deref() == Some("peaks") {
+                        return;
+                    }
+                    list_ref.borrow_mut().as_mut().unwrap().push(item.into());
+                }
+            },
+        );
+        let mut list = self.wait_for_result(operation).and_then(|_| {
+            list.borrow_mut().take().ok_or(PulseServerError::Misc(
+                "get_source_output_list(): failed to wait for operation",
+            ))
+        })?;
+
+        // Streams recording from a monitor capture what is played back, not the microphone
+        let monitors: Vec<u32> = self
+            .get_sources()?
+            .into_iter()
+            .filter(|source| source.monitor_of_sink.is_some())
+            .map(|source| source.index)
+            .collect();
+        for stream in &mut list {
+            stream.monitor = monitors.contains(&stream.device);
+        }
+        Ok(list)
+    }
+
+    fn set_default_sink(&mut self, sink: &str) -> bool {
+        let to_move = match self.get_default_sink() {
+            Ok(default_sink) => self.get_sink_inputs(default_sink.index),
+            Err(_) => return false,
+        };
         let set_default_success = Rc::new(RefCell::new(false));
         let set_default_success_ref = set_default_success.clone();
         let op = self
@@ -547,7 +1210,11 @@ impl PulseServer {
         true
     }
 
-    fn set_default_source(&mut self, sink: &str, to_move: Vec<u32>) -> bool {
+    fn set_default_source(&mut self, sink: &str) -> bool {
+        let to_move = match self.get_default_source() {
+            Ok(default_source) => self.get_source_outputs(default_source.index),
+            Err(_) => return false,
+        };
         let set_default_success = Rc::new(RefCell::new(false));
         let set_default_success_ref = set_default_success.clone();
         let op = self
@@ -578,7 +1245,7 @@ impl PulseServer {
         true
     }
 
-    fn get_default_sink(&mut self) -> Result<DeviceInfo, PulseServerError> {
+    fn get_default_sink(&mut self) -> Result<DeviceInfo, Self::Error> {
         let server_info = self.get_server_info();
         match server_info {
             Ok(info) => {
@@ -603,7 +1270,7 @@ impl PulseServer {
         }
     }
 
-    fn get_default_source(&mut self) -> Result<DeviceInfo, PulseServerError> {
+    fn get_default_source(&mut self) -> Result<DeviceInfo, Self::Error> {
         let server_info = self.get_server_info();
         match server_info {
             Ok(info) => {
@@ -652,63 +1319,80 @@ impl PulseServer {
         let _ = self.wait_for_result(op);
     }
 
-    fn get_source_outputs(&mut self, source: u32) -> Vec<u32> {
-        let result = Rc::new(RefCell::new(Vec::new()));
-        let result_ref = Rc::new(RefCell::new(Vec::new()));
-        let op = self.introspector.get_source_output_info_list(move |list| {
-            if let ListResult::Item(item) = list {
-                if source == item.source {
-                    result.borrow_mut().push(item.index);
-                }
-            }
-        });
-        let _ = self.wait_for_result(op);
-        result_ref.replace(Vec::new())
+    fn set_sink_mute_by_name(&mut self, name: &str, mute: bool) {
+        let op = self.introspector.set_sink_mute_by_name(name, mute, None);
+        self.wait_for_result(op).ok();
     }
 
-    fn get_sink_inputs(&mut self, sink: u32) -> Vec<u32> {
-        let result = Rc::new(RefCell::new(Vec::new()));
-        let result_ref = Rc::new(RefCell::new(Vec::new()));
-        let op = self.introspector.get_sink_input_info_list(move |list| {
-            if let ListResult::Item(item) = list {
-                if sink == item.sink {
-                    result.borrow_mut().push(item.index);
-                }
-            }
-        });
-        let _ = self.wait_for_result(op);
-        result_ref.replace(Vec::new())
+    fn set_source_mute_by_name(&mut self, name: &str, mute: bool) {
+        let op = self.introspector.set_source_mute_by_name(name, mute, None);
+        self.wait_for_result(op).ok();
     }
 
-    // after building an operation such as get_devices() we need to keep polling
-    // the pulse audio server to "wait" for the operation to complete
-    fn wait_for_result<G: ?Sized>(
-        &self,
-        operation: pulse::operation::Operation<G>,
-    ) -> Result<(), PulseServerError> {
-        // TODO: make this loop async. It is already in an async context, so
-        // we could make this thread sleep while waiting for the pulse server's
-        // response.
-        loop {
-            match self.mainloop.borrow_mut().iterate(false) {
-                IterateResult::Err(e) => {
-                    return Err(PulseServerError::IterateErr(IterateResult::Err(e)))
-                }
-                IterateResult::Quit(e) => {
-                    return Err(PulseServerError::IterateErr(IterateResult::Quit(e)))
-                }
-                IterateResult::Success(_) => {}
-            }
-            match operation.get_state() {
-                pulse::operation::State::Done => return Ok(()),
-                pulse::operation::State::Running => {}
-                pulse::operation::State::Cancelled => {
-                    return Err(PulseServerError::OperationErr(
-                        pulse::operation::State::Cancelled,
-                    ))
-                }
-            }
-        }
+    fn set_card_profile(&mut self, index: u32, profile: &str) {
+        let op = self
+            .introspector
+            .set_card_profile_by_index(index, profile, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn set_sink_port_by_name(&mut self, name: &str, port: &str) {
+        let op = self.introspector.set_sink_port_by_name(name, port, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn set_source_port_by_name(&mut self, name: &str, port: &str) {
+        let op = self.introspector.set_source_port_by_name(name, port, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn set_sink_input_volume(&mut self, index: u32, volume: &ChannelVolumes) {
+        let op = self
+            .introspector
+            .set_sink_input_mute(index, volume.is_muted(), None);
+        self.wait_for_result(op).ok();
+
+        let op = self.introspector.set_sink_input_volume(index, volume, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn set_source_output_volume(&mut self, index: u32, volume: &ChannelVolumes) {
+        let op = self
+            .introspector
+            .set_source_output_mute(index, volume.is_muted(), None);
+        self.wait_for_result(op).ok();
+
+        let op = self
+            .introspector
+            .set_source_output_volume(index, volume, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn set_sink_input_mute(&mut self, index: u32, mute: bool) {
+        let op = self.introspector.set_sink_input_mute(index, mute, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn set_source_output_mute(&mut self, index: u32, mute: bool) {
+        let op = self.introspector.set_source_output_mute(index, mute, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn move_sink_input(&mut self, index: u32, sink: &str) {
+        let op = self.introspector.move_sink_input_by_name(index, sink, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn move_source_output(&mut self, index: u32, source: &str) {
+        let op = self
+            .introspector
+            .move_source_output_by_name(index, source, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn kill_source_output(&mut self, index: u32) {
+        let op = self.introspector.kill_source_output(index, |_| {});
+        self.wait_for_result(op).ok();
     }
 }
 
@@ -717,36 +1401,199 @@ pub struct DeviceInfo {
     pub name: Option<String>,
     pub description: Option<String>,
     pub volume: ChannelVolumes,
+    pub channel_map: Map,
     pub mute: bool,
     pub index: u32,
+    /// Index of the card the device belongs to, if any.
+    pub card: Option<u32>,
+    pub ports: Vec<PortInfo>,
+    pub active_port: Option<String>,
+    /// Index of the sink a source monitors. Always `None` for sinks.
+    pub monitor_of_sink: Option<u32>,
 }
 
+#[cfg(not(feature = "pipewire"))]
 impl<'a> From<&SinkInfo<'a>> for DeviceInfo {
     fn from(info: &SinkInfo<'a>) -> Self {
         Self {
             name: info.name.clone().map(|x| x.into_owned()),
             description: info.description.clone().map(|x| x.into_owned()),
             volume: info.volume,
+            channel_map: info.channel_map,
             mute: info.mute,
             index: info.index,
+            card: info.card,
+            ports: info.ports.iter().map(PortInfo::from).collect(),
+            active_port: info
+                .active_port
+                .as_ref()
+                .and_then(|port| port.name.clone().map(|x| x.into_owned())),
+            monitor_of_sink: None,
         }
     }
 }
 
+#[cfg(not(feature = "pipewire"))]
 impl<'a> From<&SourceInfo<'a>> for DeviceInfo {
     fn from(info: &SourceInfo<'a>) -> Self {
         Self {
             name: info.name.clone().map(|x| x.into_owned()),
             description: info.description.clone().map(|x| x.into_owned()),
             volume: info.volume,
+            channel_map: info.channel_map,
             mute: info.mute,
             index: info.index,
+            card: info.card,
+            ports: info.ports.iter().map(PortInfo::from).collect(),
+            active_port: info
+                .active_port
+                .as_ref()
+                .and_then(|port| port.name.clone().map(|x| x.into_owned())),
+            monitor_of_sink: info.monitor_of_sink,
+        }
+    }
+}
+
+/// A port of a sink or source, such as headphones or speakers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortInfo {
+    pub name: String,
+    pub description: Option<String>,
+    /// Whether something is plugged into the port. Ports that can't detect this count as
+    /// available.
+    pub available: bool,
+}
+
+#[cfg(not(feature = "pipewire"))]
+impl<'a> From<&SinkPortInfo<'a>> for PortInfo {
+    fn from(info: &SinkPortInfo<'a>) -> Self {
+        Self {
+            name: info.name.clone().unwrap_or_default().into_owned(),
+            description: info.description.clone().map(|x| x.into_owned()),
+            available: info.available != PortAvailable::No,
+        }
+    }
+}
+
+#[cfg(not(feature = "pipewire"))]
+impl<'a> From<&SourcePortInfo<'a>> for PortInfo {
+    fn from(info: &SourcePortInfo<'a>) -> Self {
+        Self {
+            name: info.name.clone().unwrap_or_default().into_owned(),
+            description: info.description.clone().map(|x| x.into_owned()),
+            available: info.available != PortAvailable::No,
+        }
+    }
+}
+
+/// A sound card, with the profiles that decide which sinks and sources it provides
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardInfo {
+    pub index: u32,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub profiles: Vec<CardProfile>,
+    pub active_profile: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardProfile {
+    pub name: String,
+    pub description: Option<String>,
+    pub available: bool,
+}
+
+#[cfg(not(feature = "pipewire"))]
+impl<'a> From<&PulseCardInfo<'a>> for CardInfo {
+    fn from(info: &PulseCardInfo<'a>) -> Self {
+        Self {
+            index: info.index,
+            name: info.name.clone().map(|x| x.into_owned()),
+            description: info
+                .proplist
+                .get_str(pulse::proplist::properties::DEVICE_DESCRIPTION),
+            profiles: info
+                .profiles
+                .iter()
+                .map(|profile| CardProfile {
+                    name: profile.name.clone().unwrap_or_default().into_owned(),
+                    description: profile.description.clone().map(|x| x.into_owned()),
+                    available: profile.available,
+                })
+                .collect(),
+            active_profile: info
+                .active_profile
+                .as_ref()
+                .and_then(|profile| profile.name.clone().map(|x| x.into_owned())),
         }
     }
 }
 
 impl Eq for DeviceInfo {}
 
+/// A playback stream (sink input) or recording stream (source output) of an application
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    pub index: u32,
+    /// Index of the sink or source the stream is connected to.
+    pub device: u32,
+    /// Media name, such as the title of a browser tab.
+    pub name: Option<String>,
+    pub application: Option<String>,
+    pub icon_name: Option<String>,
+    pub volume: ChannelVolumes,
+    pub mute: bool,
+    /// Whether the stream is paused.
+    pub corked: bool,
+    /// Whether a recording stream captures a monitor source rather than a real input.
+    pub monitor: bool,
+}
+
+#[cfg(not(feature = "pipewire"))]
+impl<'a> From<&SinkInputInfo<'a>> for StreamInfo {
+    fn from(info: &SinkInputInfo<'a>) -> Self {
+        Self {
+            index: info.index,
+            device: info.sink,
+            name: info.name.clone().map(|x| x.into_owned()),
+            application: info
+                .proplist
+                .get_str(pulse::proplist::properties::APPLICATION_NAME),
+            icon_name: info
+                .proplist
+                .get_str(pulse::proplist::properties::APPLICATION_ICON_NAME),
+            volume: info.volume,
+            mute: info.mute,
+            corked: info.corked,
+            monitor: false,
+        }
+    }
+}
+
+#[cfg(not(feature = "pipewire"))]
+impl<'a> From<&SourceOutputInfo<'a>> for StreamInfo {
+    fn from(info: &SourceOutputInfo<'a>) -> Self {
+        Self {
+            index: info.index,
+            device: info.source,
+            name: info.name.clone().map(|x| x.into_owned()),
+            application: info
+                .proplist
+                .get_str(pulse::proplist::properties::APPLICATION_NAME),
+            icon_name: info
+                .proplist
+                .get_str(pulse::proplist::properties::APPLICATION_ICON_NAME),
+            volume: info.volume,
+            mute: info.mute,
+            corked: info.corked,
+            monitor: false,
+        }
+    }
+}
+
+impl Eq for StreamInfo {}
+
+#[cfg(not(feature = "pipewire"))]
 #[derive(Debug)]
 pub struct ServerInfo {
     /// User name of the daemon process.
@@ -769,6 +1616,7 @@ pub struct ServerInfo {
     //pub channel_map: channelmap::Map,
 }
 
+#[cfg(not(feature = "pipewire"))]
 impl<'a> From<&'a pulse::context::introspect::ServerInfo<'a>> for ServerInfo {
     fn from(info: &'a pulse::context::introspect::ServerInfo<'a>) -> Self {
         Self {
@@ -784,3 +1632,16 @@ impl<'a> From<&'a pulse::context::introspect::ServerInfo<'a>> for ServerInfo {
         }
     }
 }
+
+#[cfg(all(test, not(feature = "pipewire")))]
+mod tests {
+    use super::*;
+    use crate::test_server::{check_server, TestServer};
+
+    #[tokio::test]
+    #[ignore = "starts a headless pipewire and pipewire-pulse"]
+    async fn pulse_server() {
+        let _pipewire = TestServer::start(true);
+        check_server::<Server>().await;
+    }
+}
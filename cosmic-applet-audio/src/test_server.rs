@@ -0,0 +1,265 @@
+//! A headless PipeWire with a null sink and source, for testing the `AudioServer`s against.
+//!
+//! It needs `pipewire`, `wireplumber`, and `pw-cli`, plus `pipewire-pulse` for the pulse backend.
+//! Its sockets and state live in a temporary directory, so it doesn't touch the user's session,
+//! but it does point this process's clients at it, so the tests using it are `#[ignore]`d.
+
+use std::{
+    env,
+    io::Write,
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    pulse::{wait_for_changes, AudioServer, Changes, DeviceInfo},
+    volume::{percent_to_volume, volume_to_percent},
+};
+
+pub const SINK: &str = "cosmic-applet-audio-test-sink";
+pub const SOURCE: &str = "cosmic-applet-audio-test-source";
+/// Sink that is added and removed while the server is connected.
+pub const EXTRA_SINK: &str = "cosmic-applet-audio-test-extra-sink";
+
+/// How long to wait for the daemons to start, and for the server to report a change.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct TestServer {
+    children: Vec<Child>,
+    dir: tempfile::TempDir,
+}
+
+impl TestServer {
+    /// Start PipeWire, with `pipewire-pulse` if `pulse` is set, and create `SINK` and `SOURCE`.
+    pub fn start(pulse: bool) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        for var in [
+            "XDG_RUNTIME_DIR",
+            "PIPEWIRE_RUNTIME_DIR",
+            "XDG_CONFIG_HOME",
+            "XDG_STATE_HOME",
+        ] {
+            env::set_var(var, path);
+        }
+        env::remove_var("PIPEWIRE_REMOTE");
+        env::set_var(
+            "PULSE_SERVER",
+            format!("unix:{}", path.join("pulse").join("native").display()),
+        );
+
+        let mut server = Self {
+            children: Vec::new(),
+            dir,
+        };
+        server.spawn("pipewire");
+        wait_for_path(&server.dir.path().join("pipewire-0"));
+        // Turns the configured defaults into the actual ones
+        server.spawn("wireplumber");
+        if pulse {
+            server.spawn("pipewire-pulse");
+            wait_for_path(&server.dir.path().join("pulse").join("native"));
+        }
+
+        let status = Command::new("pw-cli")
+            .args([
+                "create-node",
+                "adapter",
+                &null_device(SINK, "Audio/Sink", true),
+            ])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let status = Command::new("pw-cli")
+            .args([
+                "create-node",
+                "adapter",
+                &null_device(SOURCE, "Audio/Source", true),
+            ])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        server
+    }
+
+    fn spawn(&mut self, program: &str) {
+        let child = Command::new(program)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap_or_else(|err| panic!("failed to start {}: {}", program, err));
+        self.children.push(child);
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        for child in self.children.iter_mut().rev() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Add a null sink, which is removed again once the returned `pw-cli` is killed.
+pub fn add_sink(name: &str) -> Child {
+    let mut pw_cli = Command::new("pw-cli")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    // Without `object.linger` the node belongs to `pw-cli`, which keeps running while its stdin
+    // stays open
+    let command = format!(
+        "create-node adapter {}\n",
+        null_device(name, "Audio/Sink", false)
+    );
+    pw_cli
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(command.as_bytes())
+        .unwrap();
+    pw_cli
+}
+
+fn null_device(name: &str, media_class: &str, linger: bool) -> String {
+    format!(
+        "{{ factory.name=support.null-audio-sink node.name={} media.class={} \
+         audio.position=[ FL FR ] object.linger={} }}",
+        name, media_class, linger
+    )
+}
+
+fn wait_for_path(path: &Path) {
+    let start = Instant::now();
+    while !path.exists() {
+        assert!(
+            start.elapsed() < TIMEOUT,
+            "{} wasn't created",
+            path.display()
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// Dispatch `server` until `done` holds, returning the changes seen on the way
+async fn wait_until<S: AudioServer>(
+    server: &mut S,
+    done: impl Fn(&mut S, &Changes) -> bool,
+) -> Changes {
+    let mut seen = Changes::default();
+    let wait = async {
+        loop {
+            let changes = server.take_changes().unwrap();
+            seen.sinks |= changes.sinks;
+            seen.sources |= changes.sources;
+            seen.sink_inputs |= changes.sink_inputs;
+            seen.source_outputs |= changes.source_outputs;
+            seen.cards |= changes.cards;
+            seen.added_sinks.extend(changes.added_sinks);
+            seen.removed_sinks.extend(changes.removed_sinks);
+            seen.added_sources.extend(changes.added_sources);
+            seen.removed_sources.extend(changes.removed_sources);
+            if done(server, &seen) {
+                return;
+            }
+            wait_for_changes(server.poll_fds()).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, wait)
+        .await
+        .expect("timed out waiting for the server");
+    seen
+}
+
+fn device<S: AudioServer>(server: &S, name: &str) -> Option<DeviceInfo> {
+    let mut devices = server.get_sinks().unwrap();
+    devices.extend(server.get_sources().unwrap());
+    devices
+        .into_iter()
+        .find(|device| device.name.as_deref() == Some(name))
+}
+
+fn percent<S: AudioServer>(server: &S, name: &str) -> Option<f64> {
+    device(server, name).map(|device| volume_to_percent(device.volume.avg()).round())
+}
+
+fn muted<S: AudioServer>(server: &S, name: &str) -> Option<bool> {
+    device(server, name).map(|device| device.mute)
+}
+
+/// Drive `S` through what the applet does with a server, against a running `TestServer`.
+pub async fn check_server<S: AudioServer>() {
+    let mut server = S::connect().expect("failed to connect");
+    assert!(server.is_connected());
+    assert!(device(&server, SINK).is_some());
+    assert!(device(&server, SOURCE).is_some());
+
+    for (name, is_sink) in [(SINK, true), (SOURCE, false)] {
+        if is_sink {
+            assert!(server.set_default_sink(name));
+        } else {
+            assert!(server.set_default_source(name));
+        }
+        wait_until(&mut server, |server, _| {
+            let default = if is_sink {
+                server.get_default_sink()
+            } else {
+                server.get_default_source()
+            };
+            default.unwrap().name.as_deref() == Some(name)
+        })
+        .await;
+
+        let changed = |changes: &Changes| {
+            if is_sink {
+                changes.sinks
+            } else {
+                changes.sources
+            }
+        };
+        let mut volume = device(&server, name).unwrap().volume;
+        volume.set(volume.len(), percent_to_volume(40.));
+        if is_sink {
+            server.set_sink_volume_by_name(name, &volume);
+        } else {
+            server.set_source_volume_by_name(name, &volume);
+        }
+        wait_until(&mut server, |server, changes| {
+            changed(changes) && percent(server, name) == Some(40.)
+        })
+        .await;
+
+        for mute in [true, false] {
+            if is_sink {
+                server.set_sink_mute_by_name(name, mute);
+            } else {
+                server.set_source_mute_by_name(name, mute);
+            }
+            wait_until(&mut server, |server, changes| {
+                changed(changes) && muted(server, name) == Some(mute)
+            })
+            .await;
+        }
+        // Muting leaves the volume alone
+        assert_eq!(percent(&server, name), Some(40.));
+    }
+
+    let mut pw_cli = add_sink(EXTRA_SINK);
+    let changes = wait_until(&mut server, |server, changes| {
+        changes.sinks && device(server, EXTRA_SINK).is_some()
+    })
+    .await;
+    let index = device(&server, EXTRA_SINK).unwrap().index;
+    assert!(changes.added_sinks.contains(&index));
+
+    pw_cli.kill().unwrap();
+    pw_cli.wait().unwrap();
+    wait_until(&mut server, |server, changes| {
+        changes.removed_sinks.contains(&index) && device(server, EXTRA_SINK).is_none()
+    })
+    .await;
+}
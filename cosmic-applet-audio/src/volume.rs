@@ -0,0 +1,314 @@
+//! Volume math on the per-channel volumes of a device or stream.
+//!
+//! These mirror PulseAudio's `pa_cvolume_scale`, `pa_cvolume_get_balance`, and friends, but work
+//! on plain slices so they don't need a running server.
+
+use libpulse_binding::{channelmap::Position, volume::Volume};
+
+pub fn volume_to_percent(volume: Volume) -> f64 {
+    volume.0 as f64 * 100. / Volume::NORMAL.0 as f64
+}
+
+pub fn percent_to_volume(percent: f64) -> Volume {
+    Volume(
+        (percent / 100. * Volume::NORMAL.0 as f64)
+            .clamp(0., Volume::UI_MAX.0 as f64)
+            .round() as u32,
+    )
+}
+
+/// Icon for an output volume, going overamplified above 100%.
+pub fn output_icon_name(volume: Volume, mute: bool) -> &'static str {
+    let output_volume = volume_to_percent(volume);
+    if mute || volume.is_muted() {
+        "audio-volume-muted-symbolic"
+    } else if output_volume < 33. {
+        "audio-volume-low-symbolic"
+    } else if output_volume < 66. {
+        "audio-volume-medium-symbolic"
+    } else if output_volume <= 100. {
+        "audio-volume-high-symbolic"
+    } else {
+        "audio-volume-overamplified-symbolic"
+    }
+}
+
+/// Icon for an input volume.
+pub fn input_icon_name(volume: Volume, mute: bool) -> &'static str {
+    let input_volume = volume_to_percent(volume);
+    if mute || volume.is_muted() || input_volume == 0.0 {
+        "microphone-sensitivity-muted-symbolic"
+    } else if input_volume < 33. {
+        "microphone-sensitivity-low-symbolic"
+    } else if input_volume < 66. {
+        "microphone-sensitivity-medium-symbolic"
+    } else {
+        "microphone-sensitivity-high-symbolic"
+    }
+}
+
+/// Loudest channel, which is what a device's master volume is shown as.
+pub fn max(volumes: &[Volume]) -> Volume {
+    volumes.iter().copied().max().unwrap_or(Volume::MUTED)
+}
+
+/// Set the loudest channel to `max`, keeping the ratios between channels.
+pub fn scale(volumes: &mut [Volume], max: Volume) {
+    let current = self::max(volumes);
+    for volume in volumes {
+        *volume = if current.0 == 0 {
+            max
+        } else {
+            Volume((volume.0 as u64 * max.0 as u64 / current.0 as u64) as u32)
+        };
+    }
+}
+
+fn on_left(position: Position) -> bool {
+    matches!(
+        position,
+        Position::FrontLeft
+            | Position::RearLeft
+            | Position::FrontLeftOfCenter
+            | Position::SideLeft
+            | Position::TopFrontLeft
+            | Position::TopRearLeft
+    )
+}
+
+fn on_right(position: Position) -> bool {
+    matches!(
+        position,
+        Position::FrontRight
+            | Position::RearRight
+            | Position::FrontRightOfCenter
+            | Position::SideRight
+            | Position::TopFrontRight
+            | Position::TopRearRight
+    )
+}
+
+fn on_front(position: Position) -> bool {
+    matches!(
+        position,
+        Position::FrontLeft
+            | Position::FrontRight
+            | Position::FrontCenter
+            | Position::FrontLeftOfCenter
+            | Position::FrontRightOfCenter
+            | Position::TopFrontLeft
+            | Position::TopFrontRight
+            | Position::TopFrontCenter
+    )
+}
+
+fn on_rear(position: Position) -> bool {
+    matches!(
+        position,
+        Position::RearLeft
+            | Position::RearRight
+            | Position::RearCenter
+            | Position::TopRearLeft
+            | Position::TopRearRight
+            | Position::TopRearCenter
+    )
+}
+
+/// Whether the channel map has channels on both the left and the right.
+pub fn can_balance(map: &[Position]) -> bool {
+    map.iter().any(|p| on_left(*p)) && map.iter().any(|p| on_right(*p))
+}
+
+/// Whether the channel map has channels both in front and behind.
+pub fn can_fade(map: &[Position]) -> bool {
+    map.iter().any(|p| on_front(*p)) && map.iter().any(|p| on_rear(*p))
+}
+
+/// Balance from -1.0 (left only) to 1.0 (right only).
+pub fn balance(map: &[Position], volumes: &[Volume]) -> f32 {
+    ratio(map, volumes, on_left, on_right)
+}
+
+pub fn set_balance(map: &[Position], volumes: &mut [Volume], balance: f32) {
+    set_ratio(map, volumes, balance, on_left, on_right)
+}
+
+/// Fade from -1.0 (front only) to 1.0 (rear only).
+pub fn fade(map: &[Position], volumes: &[Volume]) -> f32 {
+    ratio(map, volumes, on_front, on_rear)
+}
+
+pub fn set_fade(map: &[Position], volumes: &mut [Volume], fade: f32) {
+    set_ratio(map, volumes, fade, on_front, on_rear)
+}
+
+// Average volume of the channels at positions matching `side`
+fn side_avg(map: &[Position], volumes: &[Volume], side: fn(Position) -> bool) -> u32 {
+    let side_volumes: Vec<u64> = map
+        .iter()
+        .zip(volumes)
+        .filter(|(p, _)| side(**p))
+        .map(|(_, v)| v.0 as u64)
+        .collect();
+    if side_volumes.is_empty() {
+        return 0;
+    }
+    (side_volumes.iter().sum::<u64>() / side_volumes.len() as u64) as u32
+}
+
+fn ratio(
+    map: &[Position],
+    volumes: &[Volume],
+    a: fn(Position) -> bool,
+    b: fn(Position) -> bool,
+) -> f32 {
+    let a = side_avg(map, volumes, a);
+    let b = side_avg(map, volumes, b);
+    if a == b {
+        0.
+    } else if b > a {
+        1. - a as f32 / b as f32
+    } else {
+        b as f32 / a as f32 - 1.
+    }
+}
+
+fn set_ratio(
+    map: &[Position],
+    volumes: &mut [Volume],
+    ratio: f32,
+    a: fn(Position) -> bool,
+    b: fn(Position) -> bool,
+) {
+    let ratio = ratio.clamp(-1., 1.);
+    let old_a = side_avg(map, volumes, a);
+    let old_b = side_avg(map, volumes, b);
+    let max = old_a.max(old_b);
+    let (new_a, new_b) = if ratio <= 0. {
+        (max, ((ratio + 1.) * max as f32) as u32)
+    } else {
+        (((1. - ratio) * max as f32) as u32, max)
+    };
+
+    for (position, volume) in map.iter().zip(volumes) {
+        let (old, new) = if a(*position) {
+            (old_a, new_a)
+        } else if b(*position) {
+            (old_b, new_b)
+        } else {
+            continue;
+        };
+        volume.0 = if old == 0 {
+            new
+        } else {
+            (volume.0 as u64 * new as u64 / old as u64) as u32
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HALF: Volume = Volume(Volume::NORMAL.0 / 2);
+    const MONO: [Position; 1] = [Position::Mono];
+    const STEREO: [Position; 2] = [Position::FrontLeft, Position::FrontRight];
+    const QUAD: [Position; 4] = [
+        Position::FrontLeft,
+        Position::FrontRight,
+        Position::RearLeft,
+        Position::RearRight,
+    ];
+
+    fn raw(volumes: &[Volume]) -> Vec<u32> {
+        volumes.iter().map(|v| v.0).collect()
+    }
+
+    #[test]
+    fn scale_keeps_channel_ratios() {
+        let mut volumes = [HALF, Volume::NORMAL];
+        scale(&mut volumes, HALF);
+        assert_eq!(raw(&volumes), [Volume::NORMAL.0 / 4, HALF.0]);
+    }
+
+    #[test]
+    fn scale_muted_channels() {
+        let mut volumes = [Volume::MUTED; 2];
+        scale(&mut volumes, HALF);
+        assert_eq!(raw(&volumes), [HALF.0; 2]);
+    }
+
+    #[test]
+    fn percent_to_volume_clamps() {
+        assert_eq!(percent_to_volume(100.), Volume::NORMAL);
+        assert_eq!(percent_to_volume(-10.), Volume::MUTED);
+        assert_eq!(percent_to_volume(1000.), Volume::UI_MAX);
+        assert_eq!(volume_to_percent(percent_to_volume(50.)), 50.);
+    }
+
+    #[test]
+    fn output_icon_thresholds() {
+        let icon = |percent| output_icon_name(percent_to_volume(percent), false);
+        assert_eq!(icon(0.), "audio-volume-muted-symbolic");
+        assert_eq!(icon(20.), "audio-volume-low-symbolic");
+        assert_eq!(icon(50.), "audio-volume-medium-symbolic");
+        assert_eq!(icon(100.), "audio-volume-high-symbolic");
+        assert_eq!(icon(120.), "audio-volume-overamplified-symbolic");
+        assert_eq!(
+            output_icon_name(Volume::NORMAL, true),
+            "audio-volume-muted-symbolic"
+        );
+    }
+
+    #[test]
+    fn input_icon_thresholds() {
+        let icon = |percent| input_icon_name(percent_to_volume(percent), false);
+        assert_eq!(icon(0.), "microphone-sensitivity-muted-symbolic");
+        assert_eq!(icon(20.), "microphone-sensitivity-low-symbolic");
+        assert_eq!(icon(50.), "microphone-sensitivity-medium-symbolic");
+        assert_eq!(icon(150.), "microphone-sensitivity-high-symbolic");
+    }
+
+    #[test]
+    fn balance_round_trip() {
+        let mut volumes = [Volume::NORMAL; 2];
+        assert_eq!(balance(&STEREO, &volumes), 0.);
+
+        set_balance(&STEREO, &mut volumes, 0.5);
+        assert_eq!(raw(&volumes), [HALF.0, Volume::NORMAL.0]);
+        assert_eq!(balance(&STEREO, &volumes), 0.5);
+
+        set_balance(&STEREO, &mut volumes, -1.);
+        assert_eq!(raw(&volumes), [Volume::NORMAL.0, 0]);
+        assert_eq!(balance(&STEREO, &volumes), -1.);
+
+        // A silenced side comes back at the volume of the other one
+        set_balance(&STEREO, &mut volumes, 0.);
+        assert_eq!(raw(&volumes), [Volume::NORMAL.0; 2]);
+    }
+
+    #[test]
+    fn fade_round_trip() {
+        let mut volumes = [Volume::NORMAL; 4];
+        set_fade(&QUAD, &mut volumes, -0.25);
+        assert_eq!(fade(&QUAD, &volumes), -0.25);
+        assert_eq!(balance(&QUAD, &volumes), 0.);
+
+        set_fade(&QUAD, &mut volumes, 0.5);
+        assert_eq!(
+            raw(&volumes),
+            [HALF.0, HALF.0, Volume::NORMAL.0, Volume::NORMAL.0]
+        );
+        assert_eq!(fade(&QUAD, &volumes), 0.5);
+    }
+
+    #[test]
+    fn balance_and_fade_need_both_sides() {
+        assert!(!can_balance(&MONO));
+        assert!(!can_fade(&MONO));
+        assert!(can_balance(&STEREO));
+        assert!(!can_fade(&STEREO));
+        assert!(can_balance(&QUAD));
+        assert!(can_fade(&QUAD));
+    }
+}
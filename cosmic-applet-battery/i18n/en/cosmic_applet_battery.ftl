@@ -5,11 +5,78 @@ balanced = Balanced
 balanced-desc = Standard performance and battery usage.
 performance = High Performance
 performance-desc = High performance and power usage.
-max-charge = Increase the lifespan of your battery by setting a maximum charge value of 80%
+max-charge = Increase the lifespan of your battery by limiting how far it charges
+charge-limit-start = Start charging at
+charge-limit-end = Stop charging at
+charge-limit-current = Charging stops at { $end }%
+charge-limit-none = Charging to 100%
+charge-limit-read-only = Changing the charge limit needs system76-power, the battery settings are only writable by root
 seconds = s
-minutes = m
+minutes = min
 hours = h
 until-empty = until empty
+until-full = until full
 power-settings = Power and Battery Settings...
 dgpu-running = Discrete GPU is active and can reduce battery life
-dgpu-applications = Applications using {$gpu_name} discrete GPU
\ No newline at end of file
+dgpu-applications = Applications using {$gpu_name} discrete GPU
+charging = Charging
+fully-charged = Fully charged
+pending-charge = Not charging
+unknown-device = Unknown device
+peripheral-notifications = Notify when device batteries are low
+peripheral-low-battery = {$name} battery low
+peripheral-low-battery-desc = {$percent}% remaining
+battery-history = Battery history
+history-hour = 1 h
+history-six-hours = 6 h
+history-day = 24 h
+history-week = 7 d
+no-history = No battery history available
+charge = Charge
+discharge-rate = Discharge rate
+rate-summary = Average {$average} W, peak {$max} W
+charging-statistics = Charging profile
+discharging-statistics = Discharging profile
+battery-details = Battery details
+health = Health
+charge-cycles = Charge cycles
+power-draw = Power draw
+energy = Energy
+energy-design = Design capacity
+voltage = Voltage
+temperature = Temperature
+technology = Technology
+vendor = Vendor
+model = Model
+serial = Serial number
+lithium-ion = Lithium-ion
+lithium-polymer = Lithium polymer
+lithium-iron-phosphate = Lithium iron phosphate
+lead-acid = Lead acid
+nickel-cadmium = Nickel-cadmium
+nickel-metal-hydride = Nickel-metal hydride
+plugged-in-not-charging = Plugged in, not charging
+plugged-in-limit-reached = Plugged in, not charging (limit reached)
+plugged-in-discharging = Plugged in, discharging
+battery-low = Battery low
+battery-critical = Battery critically low
+battery-remaining = {$percent}% remaining
+battery-critical-action = {$percent}% remaining. Plug in within a minute or the computer will {$action}.
+battery-critical-pending = Battery critically low, will {$action} soon
+snooze = Snooze
+suspend = suspend
+hibernate = hibernate
+power-off = shut down
+profile-rules = Switch power profile automatically
+profile-rules-overridden = Paused until the power source changes
+resume = Resume
+on-battery = On battery
+on-ac = Plugged in
+battery-below = On battery below {$percent}%
+performance-degraded = Limited: {$reason}
+lap-detected = the computer is on your lap
+high-operating-temperature = the temperature is too high
+profile-holds = Applications holding a power profile
+no-profile-holds = No applications are holding a power profile
+release-profile-holds = Release all holds
+profile-holds-unsupported = Profile holds are only available with power-profiles-daemon, not system76-power
@@ -1,20 +1,31 @@
-use crate::backend::{power_profile_subscription, Power, PowerProfileRequest, PowerProfileUpdate};
+use crate::backend::{
+    power_profile_subscription, ChargeThresholds, Power, PowerProfileRequest, PowerProfileUpdate,
+    ProfileStatus,
+};
 use crate::backlight::{
-    screen_backlight_subscription, ScreenBacklightRequest, ScreenBacklightUpdate,
+    screen_backlight_subscription, BacklightLevel, ScreenBacklightRequest, ScreenBacklightUpdate,
 };
-use crate::config;
+use crate::config::{self, BatteryAppletConfig, CriticalAction};
 use crate::dgpu::{dgpu_subscription, Entry, GpuUpdate};
 use crate::fl;
-use crate::upower_device::{device_subscription, DeviceDbusEvent};
+use crate::graph::Graph;
+use crate::notifications;
+use crate::profile_rules::{Condition, ProfileSwitcher};
+use crate::upower_device::{
+    battery_history, device_subscription, peripheral_subscription, BatteryDetails, BatteryHistory,
+    DeviceDbusEvent, DeviceState, Peripheral, PeripheralDbusEvent, Technology,
+};
 use crate::upower_kbdbacklight::{
     kbd_backlight_subscription, KeyboardBacklightRequest, KeyboardBacklightUpdate,
 };
+use crate::warning::{run_critical_action, BatteryLevel};
 use cosmic::applet::cosmic_panel_config::PanelAnchor;
 use cosmic::applet::token::subscription::{
     activation_token_subscription, TokenRequest, TokenUpdate,
 };
 use cosmic::applet::{menu_button, padded_control};
 use cosmic::cctk::sctk::reexports::calloop;
+use cosmic::cosmic_config::CosmicConfigEntry;
 use cosmic::iced::alignment::Horizontal;
 use cosmic::iced::wayland::popup::{destroy_popup, get_popup};
 use cosmic::iced::{
@@ -26,29 +37,29 @@ use cosmic::iced_core::{Background, Border, Color, Shadow};
 use cosmic::iced_runtime::core::layout::Limits;
 use cosmic::iced_style::application;
 use cosmic::iced_widget::{Column, Row};
-use cosmic::widget::{divider, horizontal_space, icon, scrollable, vertical_space};
+use cosmic::widget::{button, divider, horizontal_space, icon, scrollable, vertical_space};
 use cosmic::Command;
 use cosmic::{Element, Theme};
 use cosmic_time::{anim, chain, id, once_cell::sync::Lazy, Instant, Timeline};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tokio::sync::mpsc::UnboundedSender;
+use zbus::zvariant::OwnedObjectPath;
 
-// XXX improve
 // TODO: time to empty varies? needs averaging?
 fn format_duration(duration: Duration) -> String {
     let secs = duration.as_secs();
-    if secs > 60 {
-        let min = secs / 60;
-        if min > 60 {
-            format!("{}:{:02}", min / 60, min % 60)
-        } else {
-            format!("{}{}", min, fl!("minutes"))
-        }
+    let (hours, min) = (secs / 3600, secs / 60 % 60);
+    if hours > 0 && min > 0 {
+        format!("{hours} {} {min} {}", fl!("hours"), fl!("minutes"))
+    } else if hours > 0 {
+        format!("{hours} {}", fl!("hours"))
+    } else if min > 0 {
+        format!("{min} {}", fl!("minutes"))
     } else {
-        format!("{}{}", secs, fl!("seconds"))
+        format!("{secs} {}", fl!("seconds"))
     }
 }
 
@@ -56,7 +67,67 @@ pub fn run() -> cosmic::iced::Result {
     cosmic::applet::run::<CosmicBatteryApplet>(true, ())
 }
 
+const CRITICAL_ACTION_DELAY: Duration = Duration::from_secs(60);
+
 static MAX_CHARGE: Lazy<id::Toggler> = Lazy::new(id::Toggler::unique);
+static PERIPHERAL_NOTIFICATIONS: Lazy<id::Toggler> = Lazy::new(id::Toggler::unique);
+static PROFILE_RULES: Lazy<id::Toggler> = Lazy::new(id::Toggler::unique);
+
+fn display_icon_name(brightness: f64) -> String {
+    let screen_brightness = if brightness < 0.011 {
+        "off"
+    } else if brightness < 0.333 {
+        "low"
+    } else if brightness < 0.666 {
+        "medium"
+    } else {
+        "high"
+    };
+    format!("cosmic-applet-battery-display-brightness-{screen_brightness}-symbolic")
+}
+
+fn device_state_text(state: DeviceState) -> Option<String> {
+    match state {
+        DeviceState::Charging => Some(fl!("charging")),
+        DeviceState::FullyCharged => Some(fl!("fully-charged")),
+        DeviceState::PendingCharge => Some(fl!("pending-charge")),
+        DeviceState::Discharging
+        | DeviceState::PendingDischarge
+        | DeviceState::Empty
+        | DeviceState::Unknown => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum HistoryTimespan {
+    #[default]
+    Hour,
+    SixHours,
+    Day,
+    Week,
+}
+
+impl HistoryTimespan {
+    const ALL: [Self; 4] = [Self::Hour, Self::SixHours, Self::Day, Self::Week];
+
+    fn secs(self) -> u32 {
+        match self {
+            Self::Hour => 60 * 60,
+            Self::SixHours => 6 * 60 * 60,
+            Self::Day => 24 * 60 * 60,
+            Self::Week => 7 * 24 * 60 * 60,
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Self::Hour => fl!("history-hour"),
+            Self::SixHours => fl!("history-six-hours"),
+            Self::Day => fl!("history-day"),
+            Self::Week => fl!("history-week"),
+        }
+    }
+}
 
 #[derive(Clone, Default)]
 struct GPUData {
@@ -69,19 +140,39 @@ struct GPUData {
 struct CosmicBatteryApplet {
     core: cosmic::app::Core,
     icon_name: String,
-    display_icon_name: String,
     charging_limit: bool,
+    charge_thresholds: Option<ChargeThresholds>,
+    /// Whether the charge thresholds can be changed, rather than only shown
+    charge_thresholds_writable: bool,
+    charge_limit: ChargeThresholds,
     battery_percent: f64,
     on_battery: bool,
+    peripherals: Vec<Peripheral>,
+    notified_peripherals: HashSet<OwnedObjectPath>,
+    config: BatteryAppletConfig,
+    battery_details: Option<BatteryDetails>,
+    details_toggled: bool,
+    history_toggled: bool,
+    history_timespan: HistoryTimespan,
+    history: Option<BatteryHistory>,
+    battery_level: BatteryLevel,
+    warned_level: BatteryLevel,
+    snoozed_until: Option<Instant>,
+    critical_action_at: Option<Instant>,
     gpus: HashMap<PathBuf, GPUData>,
     time_remaining: Duration,
+    time_to_full: Duration,
+    battery_state: DeviceState,
     kbd_brightness: Option<f64>,
-    screen_brightness: f64,
+    backlights: Vec<BacklightLevel>,
     popup: Option<window::Id>,
     screen_sender: Option<UnboundedSender<ScreenBacklightRequest>>,
     kbd_sender: Option<UnboundedSender<KeyboardBacklightRequest>>,
     power_profile: Power,
     power_profile_sender: Option<UnboundedSender<PowerProfileRequest>>,
+    profile_switcher: ProfileSwitcher,
+    profile_status: Option<ProfileStatus>,
+    holds_toggled: bool,
     timeline: Timeline,
     token_tx: Option<calloop::channel::Sender<TokenRequest>>,
 }
@@ -116,27 +207,168 @@ impl CosmicBatteryApplet {
             format!("cosmic-applet-battery-level-{battery_percent}-{limited}{charging}symbolic",);
     }
 
-    fn update_display(&mut self, mut percent: f64) {
-        percent = percent.clamp(0.01, 1.0);
-        self.screen_brightness = percent;
-        let screen_brightness = if self.screen_brightness < 0.011 {
-            "off"
-        } else if self.screen_brightness < 0.333 {
-            "low"
-        } else if self.screen_brightness < 0.666 {
-            "medium"
-        } else {
-            "high"
+    fn set_charging_limit(&mut self, limit: bool) {
+        self.charging_limit = limit;
+        self.update_battery(self.battery_percent, self.on_battery);
+    }
+
+    fn update_battery_level(&mut self) -> Command<cosmic::app::Message<Message>> {
+        self.battery_level =
+            self.battery_level
+                .next(self.battery_percent, self.on_battery, &self.config);
+        self.warned_level = self.warned_level.min(self.battery_level);
+        if self.battery_level != BatteryLevel::Critical {
+            self.critical_action_at = None;
+        }
+        if self.battery_level <= self.warned_level
+            || self
+                .snoozed_until
+                .is_some_and(|until| Instant::now() < until)
+        {
+            return Command::none();
         }
-        .to_string();
+        self.warned_level = self.battery_level;
+        self.snoozed_until = None;
 
-        self.display_icon_name =
-            format!("cosmic-applet-battery-display-brightness-{screen_brightness}-symbolic",);
+        let percent = format!("{:.0}", self.battery_percent);
+        let action = self.config.critical_battery_action;
+        let (summary, body, icon) = match self.battery_level {
+            BatteryLevel::Critical if action != CriticalAction::Nothing => (
+                fl!("battery-critical"),
+                fl!(
+                    "battery-critical-action",
+                    percent = percent,
+                    action = critical_action_text(action)
+                ),
+                "battery-level-0-symbolic",
+            ),
+            BatteryLevel::Critical => (
+                fl!("battery-critical"),
+                fl!("battery-remaining", percent = percent),
+                "battery-level-0-symbolic",
+            ),
+            _ => (
+                fl!("battery-low"),
+                fl!("battery-remaining", percent = percent),
+                "battery-level-10-symbolic",
+            ),
+        };
+        tokio::spawn(async move {
+            if let Err(err) = notifications::notify(&summary, &body, icon).await {
+                tracing::error!(?err, "Failed to send battery warning");
+            }
+        });
+
+        if self.battery_level != BatteryLevel::Critical || action == CriticalAction::Nothing {
+            return Command::none();
+        }
+        // Give the user a chance to plug in or snooze before acting
+        self.critical_action_at = Some(Instant::now() + CRITICAL_ACTION_DELAY);
+        Command::perform(tokio::time::sleep(CRITICAL_ACTION_DELAY), |_| {
+            cosmic::app::message::app(Message::CriticalActionDue)
+        })
     }
 
-    fn set_charging_limit(&mut self, limit: bool) {
-        self.charging_limit = limit;
-        self.update_battery(self.battery_percent, self.on_battery);
+    fn write_config(&self) {
+        if let Ok(helper) =
+            cosmic::cosmic_config::Config::new(config::APP_ID, BatteryAppletConfig::VERSION)
+        {
+            if let Err(err) = self.config.write_entry(&helper) {
+                tracing::error!(?err, "Error writing config");
+            }
+        }
+    }
+
+    fn apply_profile_rules(&mut self) {
+        // Wait for the backend, or the first profile would be lost
+        if !self.config.profile_rules_enabled || self.power_profile_sender.is_none() {
+            return;
+        }
+        let profile = self.profile_switcher.update(
+            &self.config.profile_rules,
+            self.on_battery,
+            self.battery_percent,
+        );
+        if let Some(profile) = profile {
+            self.set_power_profile(profile);
+        }
+    }
+
+    fn set_power_profile(&self, profile: Power) {
+        if let Some(tx) = self.power_profile_sender.as_ref() {
+            let _ = tx.send(PowerProfileRequest::Set(profile));
+        }
+    }
+
+    fn update_peripherals(&mut self, peripherals: Vec<Peripheral>) {
+        for peripheral in &peripherals {
+            if !peripheral.low_battery {
+                self.notified_peripherals.remove(&peripheral.path);
+            } else if self.config.peripheral_low_battery_notification
+                && self.notified_peripherals.insert(peripheral.path.clone())
+            {
+                let summary = fl!("peripheral-low-battery", name = peripheral_name(peripheral));
+                let body = fl!(
+                    "peripheral-low-battery-desc",
+                    percent = format!("{:.0}", peripheral.percentage)
+                );
+                let icon = peripheral.kind.icon_name();
+                tokio::spawn(async move {
+                    if let Err(err) = notifications::notify(&summary, &body, icon).await {
+                        tracing::error!(?err, "Failed to send low battery notification");
+                    }
+                });
+            }
+        }
+        self.notified_peripherals
+            .retain(|path| peripherals.iter().any(|p| &p.path == path));
+        self.peripherals = peripherals;
+    }
+
+    fn fetch_history(&self) -> Command<cosmic::app::Message<Message>> {
+        Command::perform(battery_history(self.history_timespan.secs()), |history| {
+            let history = history
+                .map_err(|err| tracing::error!(?err, "Failed to get battery history"))
+                .ok()
+                .flatten();
+            cosmic::app::message::app(Message::History(history))
+        })
+    }
+
+    fn battery_status(&self) -> String {
+        let percent = self.battery_percent;
+        if self.on_battery {
+            return format!(
+                "{} {} ({percent:.0}%)",
+                format_duration(self.time_remaining),
+                fl!("until-empty"),
+            );
+        }
+        match self.battery_state {
+            DeviceState::Charging if !self.time_to_full.is_zero() => format!(
+                "{} {} ({percent:.0}%)",
+                format_duration(self.time_to_full),
+                fl!("until-full"),
+            ),
+            DeviceState::Charging => format!("{} ({percent:.0}%)", fl!("charging")),
+            DeviceState::FullyCharged => format!("{} ({percent:.0}%)", fl!("fully-charged")),
+            DeviceState::PendingCharge if self.charging_limit => {
+                format!("{} ({percent:.0}%)", fl!("plugged-in-limit-reached"))
+            }
+            DeviceState::PendingCharge => {
+                format!("{} ({percent:.0}%)", fl!("plugged-in-not-charging"))
+            }
+            DeviceState::Discharging | DeviceState::PendingDischarge | DeviceState::Empty => {
+                format!("{} ({percent:.0}%)", fl!("plugged-in-discharging"))
+            }
+            DeviceState::Unknown => format!("{percent:.0}%"),
+        }
+    }
+
+    fn apply_charge_thresholds(&self, thresholds: ChargeThresholds) {
+        if let Some(tx) = self.power_profile_sender.as_ref() {
+            let _ = tx.send(PowerProfileRequest::SetChargeThresholds(thresholds));
+        }
     }
 }
 
@@ -148,14 +380,28 @@ enum Message {
         on_battery: bool,
         percent: f64,
         time_to_empty: i64,
+        time_to_full: i64,
+        state: DeviceState,
     },
     SetKbdBrightness(i32),
-    SetScreenBrightness(i32),
+    SetScreenBrightness(String, i32),
     SetChargingLimit(chain::Toggler, bool),
+    SetChargeLimitStart(i32),
+    SetChargeLimitEnd(i32),
+    ApplyChargeLimit,
+    ChargeThresholds(Option<ChargeThresholds>, bool),
+    Peripherals(Vec<Peripheral>),
+    SetPeripheralNotifications(chain::Toggler, bool),
+    ConfigChanged(BatteryAppletConfig),
+    Details(BatteryDetails),
+    ToggleDetails,
+    ToggleHistory,
+    SetHistoryTimespan(HistoryTimespan),
+    History(Option<BatteryHistory>),
     UpdateKbdBrightness(Option<f64>),
-    UpdateScreenBrightness(f64),
+    UpdateScreenBrightness(Vec<BacklightLevel>),
     InitKbdBacklight(UnboundedSender<KeyboardBacklightRequest>),
-    InitScreenBacklight(UnboundedSender<ScreenBacklightRequest>, f64),
+    InitScreenBacklight(UnboundedSender<ScreenBacklightRequest>, Vec<BacklightLevel>),
     GpuOn(PathBuf, String, Option<Vec<Entry>>),
     GpuOff(PathBuf),
     ToggleGpuApps(PathBuf),
@@ -163,6 +409,13 @@ enum Message {
     InitProfile(UnboundedSender<PowerProfileRequest>, Power),
     Profile(Power),
     SelectProfile(Power),
+    SetProfileRules(chain::Toggler, bool),
+    ProfileStatus(Option<ProfileStatus>),
+    ToggleHolds,
+    ReleaseProfileHolds,
+    ResumeProfileRules,
+    SnoozeWarning,
+    CriticalActionDue,
     Frame(Instant),
     Token(TokenUpdate),
     OpenSettings,
@@ -185,7 +438,7 @@ impl cosmic::Application for CosmicBatteryApplet {
             Self {
                 core,
                 icon_name: "battery-symbolic".to_string(),
-                display_icon_name: "display-brightness-symbolic".to_string(),
+                charge_limit: ChargeThresholds::DEFAULT_LIMIT,
                 token_tx: None,
 
                 ..Default::default()
@@ -215,15 +468,89 @@ impl cosmic::Application for CosmicBatteryApplet {
                     let _ = tx.send(KeyboardBacklightRequest::Set(brightness));
                 }
             }
-            Message::SetScreenBrightness(brightness) => {
-                self.update_display((brightness as f64 / 100.0).clamp(0.01, 1.0));
+            Message::SetScreenBrightness(name, brightness) => {
+                let brightness = (brightness as f64 / 100.0).clamp(0.01, 1.0);
+                if let Some(level) = self.backlights.iter_mut().find(|l| l.name == name) {
+                    level.brightness = brightness;
+                }
                 if let Some(tx) = &self.screen_sender {
-                    let _ = tx.send(ScreenBacklightRequest::Set(self.screen_brightness));
+                    let _ = tx.send(ScreenBacklightRequest::Set(name, brightness));
                 }
             }
             Message::SetChargingLimit(chain, enable) => {
                 self.timeline.set_chain(chain).start();
                 self.set_charging_limit(enable);
+                self.apply_charge_thresholds(if enable {
+                    self.charge_limit
+                } else {
+                    ChargeThresholds::FULL
+                });
+            }
+            Message::SetChargeLimitStart(start) => {
+                let start = start.clamp(0, 98) as u8;
+                self.charge_limit.start = start;
+                self.charge_limit.end = self.charge_limit.end.max(start + 1);
+            }
+            Message::SetChargeLimitEnd(end) => {
+                let end = end.clamp(1, 99) as u8;
+                self.charge_limit.end = end;
+                self.charge_limit.start = self.charge_limit.start.min(end - 1);
+            }
+            Message::ApplyChargeLimit => {
+                self.apply_charge_thresholds(self.charge_limit);
+            }
+            Message::ChargeThresholds(thresholds, writable) => {
+                self.charge_thresholds = thresholds;
+                self.charge_thresholds_writable = writable;
+                let limited = thresholds.is_some_and(|t| t.is_limited());
+                if let Some(thresholds) = thresholds.filter(ChargeThresholds::is_limited) {
+                    self.charge_limit = thresholds;
+                }
+                if limited != self.charging_limit {
+                    self.timeline
+                        .set_chain(if limited {
+                            chain::Toggler::on(MAX_CHARGE.clone(), 1.0)
+                        } else {
+                            chain::Toggler::off(MAX_CHARGE.clone(), 1.0)
+                        })
+                        .start();
+                    self.set_charging_limit(limited);
+                }
+            }
+            Message::Peripherals(peripherals) => {
+                self.update_peripherals(peripherals);
+            }
+            Message::SetPeripheralNotifications(chain, enabled) => {
+                self.timeline.set_chain(chain).start();
+                self.config.peripheral_low_battery_notification = enabled;
+                self.write_config();
+            }
+            Message::ConfigChanged(config) => {
+                if config.profile_rules != self.config.profile_rules {
+                    self.profile_switcher.reset();
+                }
+                self.config = config;
+                self.apply_profile_rules();
+                return self.update_battery_level();
+            }
+            Message::Details(details) => {
+                self.battery_details = Some(details);
+            }
+            Message::ToggleDetails => {
+                self.details_toggled = !self.details_toggled;
+            }
+            Message::ToggleHistory => {
+                self.history_toggled = !self.history_toggled;
+                if self.history_toggled {
+                    return self.fetch_history();
+                }
+            }
+            Message::SetHistoryTimespan(timespan) => {
+                self.history_timespan = timespan;
+                return self.fetch_history();
+            }
+            Message::History(history) => {
+                self.history = history;
             }
             Message::Errored(why) => {
                 tracing::error!("{}", why);
@@ -256,6 +583,13 @@ impl cosmic::Application for CosmicBatteryApplet {
                         .max_height(1080.0);
                     if let Some(tx) = self.power_profile_sender.as_ref() {
                         let _ = tx.send(PowerProfileRequest::Get);
+                        let _ = tx.send(PowerProfileRequest::GetChargeThresholds);
+                    }
+                    if self.history_toggled {
+                        return Command::batch(vec![
+                            get_popup(popup_settings),
+                            self.fetch_history(),
+                        ]);
                     }
                     return get_popup(popup_settings);
                 }
@@ -264,9 +598,36 @@ impl cosmic::Application for CosmicBatteryApplet {
                 on_battery,
                 percent,
                 time_to_empty,
+                time_to_full,
+                state,
             } => {
                 self.update_battery(percent, on_battery);
-                self.time_remaining = Duration::from_secs(time_to_empty as u64);
+                self.time_remaining = Duration::from_secs(time_to_empty.max(0) as u64);
+                self.time_to_full = Duration::from_secs(time_to_full.max(0) as u64);
+                self.battery_state = state;
+                self.apply_profile_rules();
+                return self.update_battery_level();
+            }
+            Message::SnoozeWarning => {
+                let minutes = u64::from(self.config.snooze_minutes);
+                self.snoozed_until = Some(Instant::now() + Duration::from_secs(minutes * 60));
+                self.critical_action_at = None;
+                // Warn again about the current level once the snooze runs out
+                self.warned_level = BatteryLevel::Normal;
+            }
+            Message::CriticalActionDue => {
+                if self
+                    .critical_action_at
+                    .is_some_and(|at| Instant::now() >= at)
+                {
+                    self.critical_action_at = None;
+                    let action = self.config.critical_battery_action;
+                    tokio::spawn(async move {
+                        if let Err(err) = run_critical_action(action).await {
+                            tracing::error!(?err, "Failed to run critical battery action");
+                        }
+                    });
+                }
             }
             Message::UpdateKbdBrightness(b) => {
                 self.kbd_brightness = b;
@@ -274,17 +635,18 @@ impl cosmic::Application for CosmicBatteryApplet {
             Message::InitKbdBacklight(tx) => {
                 self.kbd_sender = Some(tx);
             }
-            Message::InitScreenBacklight(tx, brightness) => {
+            Message::InitScreenBacklight(tx, backlights) => {
                 let _ = tx.send(ScreenBacklightRequest::Get);
                 self.screen_sender = Some(tx);
-                self.update_display(brightness);
+                self.backlights = backlights;
             }
-            Message::UpdateScreenBrightness(b) => {
-                self.update_display(b);
+            Message::UpdateScreenBrightness(backlights) => {
+                self.backlights = backlights;
             }
             Message::InitProfile(tx, profile) => {
                 self.power_profile_sender.replace(tx);
                 self.power_profile = profile;
+                self.apply_profile_rules();
             }
             Message::Profile(profile) => {
                 self.power_profile = profile;
@@ -296,8 +658,38 @@ impl cosmic::Application for CosmicBatteryApplet {
                 }
             }
             Message::SelectProfile(profile) => {
+                if self.config.profile_rules_enabled {
+                    self.profile_switcher.override_rules();
+                }
+                self.profile_switcher.select(profile);
+                self.set_power_profile(profile);
+            }
+            Message::SetProfileRules(chain, enabled) => {
+                self.timeline.set_chain(chain).start();
+                self.config.profile_rules_enabled = enabled;
+                self.profile_switcher.reset();
+                self.write_config();
+                self.apply_profile_rules();
+            }
+            Message::ProfileStatus(status) => {
+                self.profile_status = status;
+            }
+            Message::ToggleHolds => {
+                self.holds_toggled = !self.holds_toggled;
+            }
+            Message::ReleaseProfileHolds => {
+                // power-profiles-daemon starts out balanced, so fall back to that
+                let profile = self
+                    .profile_switcher
+                    .restore_profile()
+                    .unwrap_or(Power::Balanced);
                 if let Some(tx) = self.power_profile_sender.as_ref() {
-                    let _ = tx.send(PowerProfileRequest::Set(profile));
+                    let _ = tx.send(PowerProfileRequest::ReleaseHolds(profile));
+                }
+            }
+            Message::ResumeProfileRules => {
+                if let Some(profile) = self.profile_switcher.resume() {
+                    self.set_power_profile(profile);
                 }
             }
             Message::CloseRequested(id) => {
@@ -401,28 +793,120 @@ impl cosmic::Application for CosmicBatteryApplet {
 
     fn view_window(&self, _id: window::Id) -> Element<Message> {
         let name = text(fl!("battery")).size(14);
-        let description = text(if !self.on_battery {
-            format!("{}%", self.battery_percent)
-        } else {
-            format!(
-                "{} {} ({:.0}%)",
-                format_duration(self.time_remaining),
-                fl!("until-empty"),
-                self.battery_percent
-            )
-        })
-        .size(10);
+        let description = text(self.battery_status()).size(10);
 
-        let mut content = vec![
-            padded_control(
-                row![
-                    icon::from_name(&*self.icon_name).size(24).symbolic(true),
-                    column![name, description]
-                ]
-                .spacing(8)
-                .align_items(Alignment::Center),
-            )
-            .into(),
+        let mut content: Vec<Element<Message>> = vec![padded_control(
+            row![
+                icon::from_name(&*self.icon_name).size(24).symbolic(true),
+                column![name, description]
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+        )
+        .into()];
+
+        if self.battery_level != BatteryLevel::Normal {
+            let warning = match self.battery_level {
+                BatteryLevel::Critical if self.critical_action_at.is_some() => fl!(
+                    "battery-critical-pending",
+                    action = critical_action_text(self.config.critical_battery_action)
+                ),
+                BatteryLevel::Critical => fl!("battery-critical"),
+                _ => fl!("battery-low"),
+            };
+            let mut warning_row = row![
+                icon::from_name("dialog-warning-symbolic")
+                    .size(24)
+                    .symbolic(true),
+                text(warning).size(14).width(Length::Fill)
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center);
+            if !self
+                .snoozed_until
+                .is_some_and(|until| Instant::now() < until)
+            {
+                warning_row = warning_row
+                    .push(button(text(fl!("snooze")).size(12)).on_press(Message::SnoozeWarning));
+            }
+            content.push(padded_control(warning_row).into());
+        }
+
+        for peripheral in &self.peripherals {
+            let mut status = format!("{:.0}%", peripheral.percentage);
+            if let Some(state) = device_state_text(peripheral.state) {
+                status = format!("{status} · {state}");
+            }
+            content.push(
+                padded_control(
+                    row![
+                        icon::from_name(peripheral.kind.icon_name())
+                            .size(24)
+                            .symbolic(true),
+                        column![
+                            text(peripheral_name(peripheral)).size(14),
+                            text(status).size(10)
+                        ]
+                    ]
+                    .spacing(8)
+                    .align_items(Alignment::Center),
+                )
+                .into(),
+            );
+        }
+
+        if !self.peripherals.is_empty() {
+            content.push(
+                padded_control(
+                    anim!(
+                        //toggler
+                        PERIPHERAL_NOTIFICATIONS,
+                        &self.timeline,
+                        fl!("peripheral-notifications"),
+                        self.config.peripheral_low_battery_notification,
+                        Message::SetPeripheralNotifications,
+                    )
+                    .text_size(14)
+                    .width(Length::Fill),
+                )
+                .into(),
+            );
+        }
+
+        content.push(padded_control(divider::horizontal::default()).into());
+        if let Some(details) = self.battery_details.as_ref() {
+            content.push(expander(
+                fl!("battery-details"),
+                self.details_toggled,
+                Message::ToggleDetails,
+            ));
+            if self.details_toggled {
+                content.append(&mut details_view(details));
+            }
+        }
+        content.push(expander(
+            fl!("battery-history"),
+            self.history_toggled,
+            Message::ToggleHistory,
+        ));
+        if self.history_toggled {
+            content.append(&mut self.history_view());
+        }
+
+        let mut performance = column![
+            text(fl!("performance")).size(14),
+            text(fl!("performance-desc")).size(10)
+        ];
+        if let Some(reason) = self
+            .profile_status
+            .as_ref()
+            .and_then(|status| status.performance_degraded.as_deref())
+        {
+            performance = performance
+                .push(text(fl!("performance-degraded", reason = degraded_text(reason))).size(10));
+        }
+
+        content.append(&mut vec![
             padded_control(divider::horizontal::default()).into(),
             menu_button(
                 row![
@@ -468,11 +952,7 @@ impl cosmic::Application for CosmicBatteryApplet {
             .into(),
             menu_button(
                 row![
-                    column![
-                        text(fl!("performance")).size(14),
-                        text(fl!("performance-desc")).size(10)
-                    ]
-                    .width(Length::Fill),
+                    performance.width(Length::Fill),
                     if matches!(self.power_profile, Power::Performance) {
                         container(
                             icon::from_name("emblem-ok-symbolic")
@@ -487,40 +967,212 @@ impl cosmic::Application for CosmicBatteryApplet {
             )
             .on_press(Message::SelectProfile(Power::Performance))
             .into(),
-            padded_control(divider::horizontal::default()).into(),
             padded_control(
                 anim!(
                     //toggler
-                    MAX_CHARGE,
+                    PROFILE_RULES,
                     &self.timeline,
-                    fl!("max-charge"),
-                    self.charging_limit,
-                    Message::SetChargingLimit,
+                    fl!("profile-rules"),
+                    self.config.profile_rules_enabled,
+                    Message::SetProfileRules,
                 )
                 .text_size(14)
                 .width(Length::Fill),
             )
             .into(),
-            padded_control(divider::horizontal::default()).into(),
-            padded_control(
-                row![
-                    icon::from_name(self.display_icon_name.as_str())
-                        .size(24)
-                        .symbolic(true),
-                    slider(
-                        1..=100,
-                        (self.screen_brightness * 100.0) as i32,
-                        Message::SetScreenBrightness
-                    ),
-                    text(format!("{:.0}%", self.screen_brightness * 100.0))
-                        .size(16)
-                        .width(Length::Fixed(40.0))
-                        .horizontal_alignment(Horizontal::Right)
-                ]
-                .spacing(12),
-            )
-            .into(),
-        ];
+        ]);
+        if self.config.profile_rules_enabled {
+            for rule in &self.config.profile_rules {
+                content.push(
+                    padded_control(
+                        row![
+                            text(condition_text(rule.condition))
+                                .size(12)
+                                .width(Length::Fill),
+                            text(profile_text(rule.profile)).size(12)
+                        ]
+                        .spacing(8),
+                    )
+                    .into(),
+                );
+            }
+            if self.profile_switcher.is_overridden() {
+                content.push(
+                    padded_control(
+                        row![
+                            text(fl!("profile-rules-overridden"))
+                                .size(12)
+                                .width(Length::Fill),
+                            button(text(fl!("resume")).size(12))
+                                .on_press(Message::ResumeProfileRules)
+                        ]
+                        .spacing(8)
+                        .align_items(Alignment::Center),
+                    )
+                    .into(),
+                );
+            }
+        }
+        if self.power_profile_sender.is_some() {
+            content.push(expander(
+                fl!("profile-holds"),
+                self.holds_toggled,
+                Message::ToggleHolds,
+            ));
+        }
+        if self.power_profile_sender.is_some() && self.holds_toggled {
+            match self.profile_status.as_ref() {
+                Some(status) if status.holds.is_empty() => {
+                    content.push(padded_control(text(fl!("no-profile-holds")).size(12)).into());
+                }
+                Some(status) => {
+                    for hold in &status.holds {
+                        let profile = hold.profile.map(profile_text).unwrap_or_default();
+                        content.push(
+                            padded_control(
+                                row![
+                                    column![
+                                        text(&hold.application_id).size(14),
+                                        text(&hold.reason).size(10)
+                                    ]
+                                    .width(Length::Fill),
+                                    text(profile).size(12)
+                                ]
+                                .spacing(8)
+                                .align_items(Alignment::Center),
+                            )
+                            .into(),
+                        );
+                    }
+                    content.push(
+                        padded_control(
+                            button(text(fl!("release-profile-holds")).size(12))
+                                .on_press(Message::ReleaseProfileHolds),
+                        )
+                        .into(),
+                    );
+                }
+                None => {
+                    content.push(
+                        padded_control(text(fl!("profile-holds-unsupported")).size(12)).into(),
+                    );
+                }
+            }
+        }
+        content.push(padded_control(divider::horizontal::default()).into());
+
+        if let Some(thresholds) = self
+            .charge_thresholds
+            .filter(|_| !self.charge_thresholds_writable)
+        {
+            let limit = if thresholds.is_limited() {
+                fl!("charge-limit-current", end = thresholds.end)
+            } else {
+                fl!("charge-limit-none")
+            };
+            content.push(
+                padded_control(
+                    column![
+                        text(limit).size(14),
+                        text(fl!("charge-limit-read-only")).size(12)
+                    ]
+                    .spacing(4),
+                )
+                .into(),
+            );
+            content.push(padded_control(divider::horizontal::default()).into());
+        } else if self.charge_thresholds.is_some() {
+            content.push(
+                padded_control(
+                    anim!(
+                        //toggler
+                        MAX_CHARGE,
+                        &self.timeline,
+                        fl!("max-charge"),
+                        self.charging_limit,
+                        Message::SetChargingLimit,
+                    )
+                    .text_size(14)
+                    .width(Length::Fill),
+                )
+                .into(),
+            );
+            if self.charging_limit {
+                content.push(
+                    padded_control(
+                        row![
+                            text(fl!("charge-limit-start"))
+                                .size(14)
+                                .width(Length::Fixed(120.0)),
+                            slider(
+                                0..=98,
+                                self.charge_limit.start as i32,
+                                Message::SetChargeLimitStart
+                            )
+                            .on_release(Message::ApplyChargeLimit),
+                            text(format!("{}%", self.charge_limit.start))
+                                .size(16)
+                                .width(Length::Fixed(40.0))
+                                .horizontal_alignment(Horizontal::Right)
+                        ]
+                        .spacing(12)
+                        .align_items(Alignment::Center),
+                    )
+                    .into(),
+                );
+                content.push(
+                    padded_control(
+                        row![
+                            text(fl!("charge-limit-end"))
+                                .size(14)
+                                .width(Length::Fixed(120.0)),
+                            slider(
+                                1..=99,
+                                self.charge_limit.end as i32,
+                                Message::SetChargeLimitEnd
+                            )
+                            .on_release(Message::ApplyChargeLimit),
+                            text(format!("{}%", self.charge_limit.end))
+                                .size(16)
+                                .width(Length::Fixed(40.0))
+                                .horizontal_alignment(Horizontal::Right)
+                        ]
+                        .spacing(12)
+                        .align_items(Alignment::Center),
+                    )
+                    .into(),
+                );
+            }
+            content.push(padded_control(divider::horizontal::default()).into());
+        }
+
+        for backlight in &self.backlights {
+            let name = backlight.name.clone();
+            let slider_row = row![
+                icon::from_name(display_icon_name(backlight.brightness))
+                    .size(24)
+                    .symbolic(true),
+                slider(
+                    1..=100,
+                    (backlight.brightness * 100.0) as i32,
+                    move |brightness| Message::SetScreenBrightness(name.clone(), brightness)
+                ),
+                text(format!("{:.0}%", backlight.brightness * 100.0))
+                    .size(16)
+                    .width(Length::Fixed(40.0))
+                    .horizontal_alignment(Horizontal::Right)
+            ]
+            .spacing(12);
+            // Only label the sliders when there is more than one to tell apart
+            if self.backlights.len() > 1 {
+                content.push(
+                    padded_control(column![text(&backlight.name).size(12), slider_row].spacing(4))
+                        .into(),
+                );
+            } else {
+                content.push(padded_control(slider_row).into());
+            }
+        }
 
         if let Some(kbd_brightness) = self.kbd_brightness {
             content.push(
@@ -661,28 +1313,46 @@ impl cosmic::Application for CosmicBatteryApplet {
 
     fn subscription(&self) -> Subscription<Message> {
         Subscription::batch(vec![
-            device_subscription(0).map(
-                |DeviceDbusEvent::Update {
-                     on_battery,
-                     percent,
-                     time_to_empty,
-                 }| Message::Update {
+            device_subscription(0).map(|event| match event {
+                DeviceDbusEvent::Update {
+                    on_battery,
+                    percent,
+                    time_to_empty,
+                    time_to_full,
+                    state,
+                } => Message::Update {
                     on_battery,
                     percent,
                     time_to_empty,
+                    time_to_full,
+                    state,
                 },
-            ),
+                DeviceDbusEvent::Details(details) => Message::Details(details),
+            }),
+            peripheral_subscription(0)
+                .map(|PeripheralDbusEvent::Update(peripherals)| Message::Peripherals(peripherals)),
+            self.core.watch_config(Self::APP_ID).map(|u| {
+                for err in u.errors {
+                    tracing::error!(?err, "Error watching config");
+                }
+                Message::ConfigChanged(u.config)
+            }),
             kbd_backlight_subscription(0).map(|event| match event {
                 KeyboardBacklightUpdate::Brightness(b) => Message::UpdateKbdBrightness(b),
                 KeyboardBacklightUpdate::Sender(tx) => Message::InitKbdBacklight(tx),
             }),
             screen_backlight_subscription(0).map(|e| match e {
-                ScreenBacklightUpdate::Update(b) => Message::UpdateScreenBrightness(b),
-                ScreenBacklightUpdate::Init(tx, b) => Message::InitScreenBacklight(tx, b),
+                ScreenBacklightUpdate::Update(levels) => Message::UpdateScreenBrightness(levels),
+                ScreenBacklightUpdate::Init(tx, levels) => Message::InitScreenBacklight(tx, levels),
             }),
             power_profile_subscription(0).map(|event| match event {
                 PowerProfileUpdate::Update { profile } => Message::Profile(profile),
                 PowerProfileUpdate::Init(tx, p) => Message::InitProfile(p, tx),
+                PowerProfileUpdate::ChargeThresholds {
+                    thresholds,
+                    writable,
+                } => Message::ChargeThresholds(thresholds, writable),
+                PowerProfileUpdate::Status(status) => Message::ProfileStatus(status),
                 PowerProfileUpdate::Error(e) => Message::Errored(e), // TODO: handle error
             }),
             dgpu_subscription(0).map(|event| match event {
@@ -704,3 +1374,222 @@ impl cosmic::Application for CosmicBatteryApplet {
         Some(cosmic::applet::style())
     }
 }
+
+impl CosmicBatteryApplet {
+    fn history_view(&self) -> Vec<Element<Message>> {
+        let timespans = HistoryTimespan::ALL.into_iter().map(|timespan| {
+            button(text(timespan.label()).size(12))
+                .style(if timespan == self.history_timespan {
+                    cosmic::theme::Button::Suggested
+                } else {
+                    cosmic::theme::Button::Standard
+                })
+                .on_press(Message::SetHistoryTimespan(timespan))
+                .into()
+        });
+        let mut content =
+            vec![
+                padded_control(Row::with_children(timespans.collect::<Vec<_>>()).spacing(8)).into(),
+            ];
+
+        let Some(history) = self.history.as_ref() else {
+            content.push(padded_control(text(fl!("no-history")).size(14)).into());
+            return content;
+        };
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default() as f64;
+        let since = now - f64::from(self.history_timespan.secs());
+        let samples = |samples: &[(u32, f64)]| -> Vec<(f64, f64)> {
+            samples
+                .iter()
+                .map(|&(time, value)| (f64::from(time), value))
+                .collect()
+        };
+
+        content.push(history_graph(
+            fl!("charge"),
+            String::new(),
+            Graph::new(samples(&history.charge))
+                .x_range(since..=now)
+                .y_range(0.0..=100.0),
+        ));
+
+        let rates = history.rate.iter().map(|&(_, rate)| rate);
+        let max_rate = rates.clone().fold(0.0, f64::max);
+        let rate_summary = if history.rate.is_empty() {
+            String::new()
+        } else {
+            fl!(
+                "rate-summary",
+                average = format!("{:.1}", rates.sum::<f64>() / history.rate.len() as f64),
+                max = format!("{:.1}", max_rate)
+            )
+        };
+        content.push(history_graph(
+            fl!("discharge-rate"),
+            rate_summary,
+            Graph::new(samples(&history.rate)).x_range(since..=now),
+        ));
+
+        for (label, statistics) in [
+            (fl!("charging-statistics"), &history.charging),
+            (fl!("discharging-statistics"), &history.discharging),
+        ] {
+            if !statistics.is_empty() {
+                content.push(history_graph(
+                    label,
+                    String::new(),
+                    Graph::new(statistics.clone())
+                        .x_range(0.0..=100.0)
+                        .bars(50)
+                        .height(32.0),
+                ));
+            }
+        }
+        content
+    }
+}
+
+fn details_view<'a>(details: &BatteryDetails) -> Vec<Element<'a, Message>> {
+    let mut rows = vec![
+        (fl!("health"), format!("{:.0}%", details.capacity)),
+        (fl!("power-draw"), format!("{:.1} W", details.energy_rate)),
+        (
+            fl!("energy"),
+            format!("{:.1} / {:.1} Wh", details.energy, details.energy_full),
+        ),
+        (
+            fl!("energy-design"),
+            format!("{:.1} Wh", details.energy_full_design),
+        ),
+        (fl!("voltage"), format!("{:.1} V", details.voltage)),
+    ];
+    if let Some(charge_cycles) = details.charge_cycles {
+        rows.insert(1, (fl!("charge-cycles"), charge_cycles.to_string()));
+    }
+    if let Some(temperature) = details.temperature {
+        rows.push((fl!("temperature"), format!("{temperature:.1} °C")));
+    }
+    if let Some(technology) = technology_text(details.technology) {
+        rows.push((fl!("technology"), technology));
+    }
+    for (label, value) in [
+        (fl!("vendor"), &details.vendor),
+        (fl!("model"), &details.model),
+        (fl!("serial"), &details.serial),
+    ] {
+        if !value.is_empty() {
+            rows.push((label, value.clone()));
+        }
+    }
+
+    rows.into_iter()
+        .map(|(label, value)| {
+            padded_control(
+                row![
+                    text(label).size(14).width(Length::Fill),
+                    text(value).size(14)
+                ]
+                .spacing(8)
+                .align_items(Alignment::Center),
+            )
+            .into()
+        })
+        .collect()
+}
+
+fn technology_text(technology: Technology) -> Option<String> {
+    match technology {
+        Technology::LithiumIon => Some(fl!("lithium-ion")),
+        Technology::LithiumPolymer => Some(fl!("lithium-polymer")),
+        Technology::LithiumIronPhosphate => Some(fl!("lithium-iron-phosphate")),
+        Technology::LeadAcid => Some(fl!("lead-acid")),
+        Technology::NickelCadmium => Some(fl!("nickel-cadmium")),
+        Technology::NickelMetalHydride => Some(fl!("nickel-metal-hydride")),
+        Technology::Unknown => None,
+    }
+}
+
+fn expander<'a>(label: String, toggled: bool, on_press: Message) -> Element<'a, Message> {
+    menu_button(row![
+        text(label)
+            .size(14)
+            .width(Length::Fill)
+            .height(Length::Fixed(24.0))
+            .vertical_alignment(Vertical::Center),
+        container(
+            icon::from_name(if toggled {
+                "go-down-symbolic"
+            } else {
+                "go-up-symbolic"
+            })
+            .size(14)
+            .symbolic(true)
+        )
+        .align_x(Horizontal::Center)
+        .align_y(Vertical::Center)
+        .width(Length::Fixed(24.0))
+        .height(Length::Fixed(24.0)),
+    ])
+    .on_press(on_press)
+    .into()
+}
+
+fn history_graph<'a>(label: String, summary: String, graph: Graph) -> Element<'a, Message> {
+    padded_control(
+        column![
+            row![
+                text(label).size(14).width(Length::Fill),
+                text(summary).size(10)
+            ]
+            .align_items(Alignment::Center),
+            graph
+        ]
+        .spacing(4),
+    )
+    .into()
+}
+
+fn degraded_text(reason: &str) -> String {
+    match reason {
+        "lap-detected" => fl!("lap-detected"),
+        "high-operating-temperature" => fl!("high-operating-temperature"),
+        reason => reason.to_string(),
+    }
+}
+
+fn condition_text(condition: Condition) -> String {
+    match condition {
+        Condition::OnBattery => fl!("on-battery"),
+        Condition::OnAc => fl!("on-ac"),
+        Condition::BatteryBelow(percent) => fl!("battery-below", percent = percent),
+    }
+}
+
+fn profile_text(profile: Power) -> String {
+    match profile {
+        Power::Battery => fl!("battery"),
+        Power::Balanced => fl!("balanced"),
+        Power::Performance => fl!("performance"),
+    }
+}
+
+fn critical_action_text(action: CriticalAction) -> String {
+    match action {
+        CriticalAction::Nothing => String::new(),
+        CriticalAction::Suspend => fl!("suspend"),
+        CriticalAction::Hibernate => fl!("hibernate"),
+        CriticalAction::PowerOff => fl!("power-off"),
+    }
+}
+
+fn peripheral_name(peripheral: &Peripheral) -> String {
+    if peripheral.model.is_empty() {
+        fl!("unknown-device")
+    } else {
+        peripheral.model.clone()
+    }
+}
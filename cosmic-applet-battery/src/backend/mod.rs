@@ -1,4 +1,9 @@
-use cosmic::iced::{self, futures::SinkExt, subscription};
+use cosmic::iced::{
+    self,
+    futures::{SinkExt, StreamExt},
+    subscription,
+};
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::hash::Hash;
 use tokio::sync::mpsc::UnboundedReceiver;
@@ -8,11 +13,13 @@ use zbus::Result;
 
 use self::power_daemon::PowerDaemonProxy;
 use self::power_profiles::PowerProfilesProxy;
+use self::sysfs::ChargeControl;
 
 mod power_daemon;
 mod power_profiles;
+mod sysfs;
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Deserialize, Serialize)]
 pub enum Power {
     Battery,
     #[default]
@@ -20,7 +27,34 @@ pub enum Power {
     Performance,
 }
 
-#[derive(Debug)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct ChargeThresholds {
+    /// Charging starts when the battery drops below this percentage.
+    pub start: u8,
+    /// Charging stops when the battery reaches this percentage.
+    pub end: u8,
+}
+
+impl ChargeThresholds {
+    /// No limit, same as the `full_charge` profile of system76-power.
+    pub const FULL: Self = Self {
+        start: 96,
+        end: 100,
+    };
+    pub const DEFAULT_LIMIT: Self = Self { start: 75, end: 80 };
+
+    pub fn is_limited(&self) -> bool {
+        self.end < 100
+    }
+}
+
+impl Default for ChargeThresholds {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Backend<'a> {
     S76PowerDaemon(PowerDaemonProxy<'a>),
     PowerProfilesDaemon(PowerProfilesProxy<'a>),
@@ -94,6 +128,149 @@ pub async fn set_power_profile(daemon: Backend<'_>, power: Power) -> Result<()>
     }
 }
 
+pub async fn get_charge_thresholds(daemon: Backend<'_>) -> Result<Option<ChargeThresholds>> {
+    match daemon {
+        Backend::S76PowerDaemon(p) => {
+            let (start, end) = p.get_charge_thresholds().await?;
+            Ok(Some(ChargeThresholds { start, end }))
+        }
+        Backend::PowerProfilesDaemon(_) => Ok(ChargeControl::default().thresholds().await?),
+    }
+}
+
+/// Whether `set_charge_thresholds` can work without further privileges.
+pub async fn charge_thresholds_writable(daemon: &Backend<'_>) -> bool {
+    match daemon {
+        Backend::S76PowerDaemon(_) => true,
+        // The applet writes the sysfs attributes itself
+        Backend::PowerProfilesDaemon(_) => ChargeControl::default().writable().await,
+    }
+}
+
+pub async fn set_charge_thresholds(
+    daemon: Backend<'_>,
+    thresholds: ChargeThresholds,
+) -> Result<()> {
+    match daemon {
+        Backend::S76PowerDaemon(p) => {
+            p.set_charge_thresholds(&(thresholds.start, thresholds.end))
+                .await
+        }
+        Backend::PowerProfilesDaemon(_) => {
+            Ok(ChargeControl::default().set_thresholds(thresholds).await?)
+        }
+    }
+}
+
+/// An application holding a power-profiles-daemon profile, e.g. performance while compiling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileHold {
+    pub application_id: String,
+    pub reason: String,
+    pub profile: Option<Power>,
+}
+
+/// State only power-profiles-daemon exposes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileStatus {
+    /// Why the performance profile is degraded, like `lap-detected`
+    pub performance_degraded: Option<String>,
+    pub holds: Vec<ProfileHold>,
+}
+
+fn ppd_profile(profile: &str) -> Option<Power> {
+    match profile {
+        "power-saver" => Some(Power::Battery),
+        "balanced" => Some(Power::Balanced),
+        "performance" => Some(Power::Performance),
+        _ => None,
+    }
+}
+
+/// `None` with system76-power, which doesn't support holds.
+pub async fn get_profile_status(daemon: Backend<'_>) -> Result<Option<ProfileStatus>> {
+    match daemon {
+        Backend::S76PowerDaemon(_) => Ok(None),
+        Backend::PowerProfilesDaemon(ppd) => {
+            let degraded = ppd.performance_degraded().await?;
+            let holds = ppd
+                .active_profile_holds()
+                .await?
+                .into_iter()
+                .map(|hold| {
+                    let string = |key: &str| {
+                        hold.get(key)
+                            .and_then(|value| value.downcast_ref::<str>())
+                            .unwrap_or_default()
+                            .to_string()
+                    };
+                    ProfileHold {
+                        application_id: string("ApplicationId"),
+                        reason: string("Reason"),
+                        profile: ppd_profile(&string("Profile")),
+                    }
+                })
+                .collect();
+            Ok(Some(ProfileStatus {
+                performance_degraded: (!degraded.is_empty()).then_some(degraded),
+                holds,
+            }))
+        }
+    }
+}
+
+/// Release all profile holds, switching to `profile`.
+///
+/// Hold cookies are only known to the applications holding them, but
+/// power-profiles-daemon releases every hold when a profile is picked by the user.
+pub async fn release_profile_holds(daemon: Backend<'_>, profile: Power) -> Result<()> {
+    match daemon {
+        Backend::S76PowerDaemon(_) => Ok(()),
+        daemon @ Backend::PowerProfilesDaemon(_) => set_power_profile(daemon, profile).await,
+    }
+}
+
+// Wait for power-profiles-daemon to change the profile, its holds or degraded state.
+async fn profile_changed(daemon: &Backend<'_>) {
+    match daemon {
+        Backend::S76PowerDaemon(_) => iced::futures::future::pending().await,
+        Backend::PowerProfilesDaemon(ppd) => {
+            let mut changes = futures::stream_select!(
+                ppd.receive_active_profile_changed().await.map(|_| ()),
+                ppd.receive_active_profile_holds_changed().await.map(|_| ()),
+                ppd.receive_performance_degraded_changed().await.map(|_| ()),
+            );
+            if changes.next().await.is_none() {
+                iced::futures::future::pending().await
+            }
+        }
+    }
+}
+
+async fn send_charge_thresholds(
+    backend: Backend<'_>,
+    output: &mut futures::channel::mpsc::Sender<PowerProfileUpdate>,
+) {
+    let writable = charge_thresholds_writable(&backend).await;
+    let thresholds = get_charge_thresholds(backend).await.unwrap_or_default();
+    _ = output
+        .send(PowerProfileUpdate::ChargeThresholds {
+            thresholds,
+            writable,
+        })
+        .await;
+}
+
+async fn send_profile_status(
+    backend: Backend<'_>,
+    output: &mut futures::channel::mpsc::Sender<PowerProfileUpdate>,
+) {
+    match get_profile_status(backend).await {
+        Ok(status) => _ = output.send(PowerProfileUpdate::Status(status)).await,
+        Err(e) => _ = output.send(PowerProfileUpdate::Error(e.to_string())).await,
+    }
+}
+
 pub fn power_profile_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
     id: I,
 ) -> iced::Subscription<PowerProfileUpdate> {
@@ -150,7 +327,10 @@ async fn start_listening(
                 }
             };
             // Successful connection
-            let profile = match get_power_profile(backend).await.map_err(|e| e.to_string()) {
+            let profile = match get_power_profile(backend.clone())
+                .await
+                .map_err(|e| e.to_string())
+            {
                 Ok(p) => p,
                 Err(e) => {
                     _ = output.send(PowerProfileUpdate::Error(e)).await;
@@ -163,6 +343,8 @@ async fn start_listening(
             };
             let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
             _ = output.send(PowerProfileUpdate::Init(profile, tx)).await;
+            send_charge_thresholds(backend.clone(), output).await;
+            send_profile_status(backend, output).await;
             State::Waiting(conn, rx, backend_type)
         }
         State::Waiting(conn, mut rx, backend_type) => {
@@ -177,11 +359,28 @@ async fn start_listening(
                 }
             };
 
-            match rx.recv().await {
+            // Changes made by power-profiles-daemon are handled like a request for them
+            let request = tokio::select! {
+                request = rx.recv() => request,
+                () = profile_changed(&backend) => Some(PowerProfileRequest::Get),
+            };
+
+            match request {
                 Some(PowerProfileRequest::Get) => {
-                    if let Ok(profile) = get_power_profile(backend).await {
+                    if let Ok(profile) = get_power_profile(backend.clone()).await {
                         _ = output.send(PowerProfileUpdate::Update { profile }).await;
                     }
+                    send_profile_status(backend, output).await;
+                    State::Waiting(conn, rx, backend_type)
+                }
+                Some(PowerProfileRequest::ReleaseHolds(profile)) => {
+                    if let Err(e) = release_profile_holds(backend.clone(), profile).await {
+                        _ = output.send(PowerProfileUpdate::Error(e.to_string())).await;
+                    }
+                    if let Ok(profile) = get_power_profile(backend.clone()).await {
+                        _ = output.send(PowerProfileUpdate::Update { profile }).await;
+                    }
+                    send_profile_status(backend, output).await;
                     State::Waiting(conn, rx, backend_type)
                 }
                 Some(PowerProfileRequest::Set(profile)) => {
@@ -189,6 +388,18 @@ async fn start_listening(
                     _ = output.send(PowerProfileUpdate::Update { profile }).await;
                     State::Waiting(conn, rx, backend_type)
                 }
+                Some(PowerProfileRequest::GetChargeThresholds) => {
+                    send_charge_thresholds(backend, output).await;
+                    State::Waiting(conn, rx, backend_type)
+                }
+                Some(PowerProfileRequest::SetChargeThresholds(thresholds)) => {
+                    if let Err(e) = set_charge_thresholds(backend.clone(), thresholds).await {
+                        _ = output.send(PowerProfileUpdate::Error(e.to_string())).await;
+                    }
+                    // Read back what was actually applied
+                    send_charge_thresholds(backend, output).await;
+                    State::Waiting(conn, rx, backend_type)
+                }
                 None => State::Finished,
             }
         }
@@ -200,11 +411,21 @@ async fn start_listening(
 pub enum PowerProfileRequest {
     Get,
     Set(Power),
+    GetChargeThresholds,
+    SetChargeThresholds(ChargeThresholds),
+    ReleaseHolds(Power),
 }
 
 #[derive(Debug, Clone)]
 pub enum PowerProfileUpdate {
     Init(Power, UnboundedSender<PowerProfileRequest>),
-    Update { profile: Power },
+    Update {
+        profile: Power,
+    },
+    ChargeThresholds {
+        thresholds: Option<ChargeThresholds>,
+        writable: bool,
+    },
+    Status(Option<ProfileStatus>),
     Error(String),
 }
@@ -0,0 +1,234 @@
+//! Battery charge thresholds through the kernel `power_supply` class.
+//!
+//! See `Documentation/ABI/testing/sysfs-class-power` in the kernel tree for the
+//! `charge_control_*_threshold` attributes.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::ChargeThresholds;
+
+const POWER_SUPPLY_SYSDIR: &str = "sys/class/power_supply";
+const START_THRESHOLD: &str = "charge_control_start_threshold";
+const END_THRESHOLD: &str = "charge_control_end_threshold";
+
+#[derive(Debug, Clone)]
+pub struct ChargeControl {
+    root: PathBuf,
+}
+
+impl Default for ChargeControl {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl ChargeControl {
+    /// `root` is the directory containing `sys`, so a fake sysfs tree can be used instead of `/`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    // Batteries with at least an end threshold. Some drivers don't support a start threshold.
+    async fn batteries(&self) -> io::Result<Vec<PathBuf>> {
+        let mut batteries = Vec::new();
+        let mut dir_stream = tokio::fs::read_dir(self.root.join(POWER_SUPPLY_SYSDIR)).await?;
+        while let Some(entry) = dir_stream.next_entry().await? {
+            let path = entry.path();
+            let Ok(type_) = tokio::fs::read_to_string(path.join("type")).await else {
+                continue;
+            };
+            if type_.trim() == "Battery" && exists(&path.join(END_THRESHOLD)).await {
+                batteries.push(path);
+            }
+        }
+        batteries.sort();
+        Ok(batteries)
+    }
+
+    pub async fn thresholds(&self) -> io::Result<Option<ChargeThresholds>> {
+        let Some(battery) = self.batteries().await?.into_iter().next() else {
+            return Ok(None);
+        };
+        let end = read_threshold(&battery.join(END_THRESHOLD)).await?;
+        let start = read_threshold(&battery.join(START_THRESHOLD))
+            .await
+            .unwrap_or_default();
+        Ok(Some(ChargeThresholds { start, end }))
+    }
+
+    /// Whether the thresholds of every battery can be changed. The attributes are owned by
+    /// root, so this is usually false for a user session.
+    pub async fn writable(&self) -> bool {
+        let Ok(batteries) = self.batteries().await else {
+            return false;
+        };
+        let mut writable = !batteries.is_empty();
+        for battery in batteries {
+            let start = battery.join(START_THRESHOLD);
+            writable &= can_write(&battery.join(END_THRESHOLD)).await
+                && (!exists(&start).await || can_write(&start).await);
+        }
+        writable
+    }
+
+    pub async fn set_thresholds(&self, thresholds: ChargeThresholds) -> io::Result<()> {
+        let batteries = self.batteries().await?;
+        if batteries.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no battery supports charge thresholds",
+            ));
+        }
+
+        for battery in batteries {
+            let start = battery.join(START_THRESHOLD);
+            let end = battery.join(END_THRESHOLD);
+            let has_start = exists(&start).await;
+            // Drivers may reject a start threshold that isn't below the current end threshold,
+            // so order the writes such that start < end holds after each of them.
+            let current_end = read_threshold(&end).await?;
+            if has_start && thresholds.start < current_end {
+                write_threshold(&start, thresholds.start).await?;
+                write_threshold(&end, thresholds.end).await?;
+            } else {
+                write_threshold(&end, thresholds.end).await?;
+                if has_start {
+                    write_threshold(&start, thresholds.start).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn exists(path: &Path) -> bool {
+    tokio::fs::try_exists(path).await.unwrap_or_default()
+}
+
+// Opening for writing, without writing anything, leaves the threshold as it is
+async fn can_write(path: &Path) -> bool {
+    tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .await
+        .is_ok()
+}
+
+async fn read_threshold(path: &Path) -> io::Result<u8> {
+    tokio::fs::read_to_string(path)
+        .await?
+        .trim()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+async fn write_threshold(path: &Path, value: u8) -> io::Result<()> {
+    tokio::fs::write(path, value.to_string()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // Add a power supply with the given thresholds, returning its directory
+    fn add_supply(root: &Path, name: &str, type_: &str, start: Option<u8>, end: u8) -> PathBuf {
+        let dir = root.join(POWER_SUPPLY_SYSDIR).join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("type"), format!("{type_}\n")).unwrap();
+        if let Some(start) = start {
+            fs::write(dir.join(START_THRESHOLD), format!("{start}\n")).unwrap();
+        }
+        fs::write(dir.join(END_THRESHOLD), format!("{end}\n")).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap().trim().to_owned()
+    }
+
+    #[tokio::test]
+    async fn read_back_thresholds() {
+        let root = tempfile::tempdir().unwrap();
+        add_supply(root.path(), "AC", "Mains", Some(10), 20);
+        add_supply(root.path(), "BAT0", "Battery", Some(40), 80);
+        let control = ChargeControl::new(root.path());
+        assert_eq!(
+            control.thresholds().await.unwrap(),
+            Some(ChargeThresholds { start: 40, end: 80 })
+        );
+
+        assert!(control.writable().await);
+
+        let thresholds = ChargeThresholds { start: 75, end: 90 };
+        control.set_thresholds(thresholds).await.unwrap();
+        assert_eq!(control.thresholds().await.unwrap(), Some(thresholds));
+    }
+
+    #[tokio::test]
+    async fn end_threshold_only() {
+        let root = tempfile::tempdir().unwrap();
+        let battery = add_supply(root.path(), "BAT0", "Battery", None, 80);
+        let control = ChargeControl::new(root.path());
+        assert_eq!(
+            control.thresholds().await.unwrap(),
+            Some(ChargeThresholds { start: 0, end: 80 })
+        );
+
+        control
+            .set_thresholds(ChargeThresholds { start: 50, end: 60 })
+            .await
+            .unwrap();
+        assert_eq!(read(&battery.join(END_THRESHOLD)), "60");
+        assert!(!battery.join(START_THRESHOLD).exists());
+    }
+
+    #[tokio::test]
+    async fn no_battery() {
+        let root = tempfile::tempdir().unwrap();
+        add_supply(root.path(), "AC", "Mains", None, 80);
+        let control = ChargeControl::new(root.path());
+        assert_eq!(control.thresholds().await.unwrap(), None);
+        assert!(!control.writable().await);
+        let err = control
+            .set_thresholds(ChargeThresholds { start: 40, end: 80 })
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    // A start threshold that can't be written shows whether the end threshold was written first
+    fn battery_rejecting_start(root: &Path) -> PathBuf {
+        let battery = add_supply(root, "BAT0", "Battery", None, 80);
+        fs::create_dir(battery.join(START_THRESHOLD)).unwrap();
+        battery
+    }
+
+    #[tokio::test]
+    async fn raise_writes_end_first() {
+        let root = tempfile::tempdir().unwrap();
+        let battery = battery_rejecting_start(root.path());
+        let control = ChargeControl::new(root.path());
+        assert!(control
+            .set_thresholds(ChargeThresholds { start: 85, end: 95 })
+            .await
+            .is_err());
+        assert_eq!(read(&battery.join(END_THRESHOLD)), "95");
+    }
+
+    #[tokio::test]
+    async fn lower_writes_start_first() {
+        let root = tempfile::tempdir().unwrap();
+        let battery = battery_rejecting_start(root.path());
+        let control = ChargeControl::new(root.path());
+        assert!(control
+            .set_thresholds(ChargeThresholds { start: 20, end: 30 })
+            .await
+            .is_err());
+        assert_eq!(read(&battery.join(END_THRESHOLD)), "80");
+    }
+}
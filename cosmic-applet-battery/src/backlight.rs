@@ -12,7 +12,12 @@ use std::{
 };
 
 use cosmic::iced::{self, futures::SinkExt, subscription};
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::{
+    io::unix::AsyncFd,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+
+use crate::dgpu::WrappedSocket;
 
 const BACKLIGHT_SYSDIR: &str = "/sys/class/backlight";
 
@@ -25,8 +30,33 @@ trait LogindSession {
     fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()>;
 }
 
-#[derive(Clone)]
-pub struct Backlight(String);
+/// The `type` of a backlight, in the order of preference recommended by
+/// `Documentation/ABI/stable/sysfs-class-backlight`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BacklightType {
+    Firmware,
+    Platform,
+    Raw,
+}
+
+impl FromStr for BacklightType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "firmware" => Ok(Self::Firmware),
+            "platform" => Ok(Self::Platform),
+            "raw" => Ok(Self::Raw),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Backlight {
+    name: String,
+    type_: BacklightType,
+}
 
 impl Backlight {
     pub async fn brightness(&self) -> Option<u32> {
@@ -43,35 +73,88 @@ impl Backlight {
         session: &LogindSessionProxy<'_>,
         value: u32,
     ) -> zbus::Result<()> {
-        session.set_brightness("backlight", &self.0, value).await
+        session.set_brightness("backlight", &self.name, value).await
     }
 
     async fn prop<T: FromStr>(&self, name: &str) -> Option<T> {
-        let path = Path::new(BACKLIGHT_SYSDIR).join(&self.0).join(name);
+        let path = Path::new(BACKLIGHT_SYSDIR).join(&self.name).join(name);
         let mut file = File::open(path).ok()?;
         let mut s = String::new();
         file.read_to_string(&mut s).ok()?;
         s.trim().parse().ok()
     }
+
+    async fn level(&self) -> Option<BacklightLevel> {
+        let max_brightness = self.max_brightness().await?;
+        let brightness = (self.brightness().await.unwrap_or_default() as f64
+            / max_brightness as f64)
+            .clamp(0., 1.);
+        Some(BacklightLevel {
+            name: self.name.clone(),
+            brightness,
+        })
+    }
 }
 
-// Choose backlight with most "precision". This is what `light` does.
-pub async fn backlight() -> io::Result<Option<Backlight>> {
-    let mut best_backlight = None;
-    let mut best_max_brightness = 0;
+/// Brightness of a backlight, from 0 to 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacklightLevel {
+    pub name: String,
+    pub brightness: f64,
+}
+
+// All controllable backlights, preferring firmware over platform over raw interfaces,
+// then the ones with most "precision". This is what `light` does.
+pub async fn all_backlights() -> io::Result<Vec<Backlight>> {
+    let mut backlights = Vec::new();
     let mut dir_stream = tokio::fs::read_dir(BACKLIGHT_SYSDIR).await?;
     while let Ok(Some(entry)) = dir_stream.next_entry().await {
         if let Ok(filename) = str::from_utf8(entry.file_name().as_bytes()) {
-            let backlight = Backlight(filename.to_string());
-            if let Some(max_brightness) = backlight.max_brightness().await {
-                if max_brightness > best_max_brightness {
-                    best_backlight = Some(backlight);
-                    best_max_brightness = max_brightness;
-                }
+            let mut backlight = Backlight {
+                name: filename.to_string(),
+                type_: BacklightType::Raw,
+            };
+            backlight.type_ = backlight.prop("type").await.unwrap_or(BacklightType::Raw);
+            if let Some(max_brightness) = backlight.max_brightness().await.filter(|m| *m > 0) {
+                backlights.push((backlight, max_brightness));
             }
         }
     }
-    Ok(best_backlight)
+    backlights.sort_by(|(a, a_max), (b, b_max)| a.type_.cmp(&b.type_).then(b_max.cmp(a_max)));
+    Ok(backlights.into_iter().map(|(backlight, _)| backlight).collect())
+}
+
+async fn levels(backlights: &[Backlight]) -> Vec<BacklightLevel> {
+    let mut levels = Vec::with_capacity(backlights.len());
+    for backlight in backlights {
+        levels.extend(backlight.level().await);
+    }
+    levels
+}
+
+fn backlight_monitor() -> io::Result<AsyncFd<WrappedSocket>> {
+    AsyncFd::new(WrappedSocket(
+        udev::MonitorBuilder::new()?
+            .match_subsystem("backlight")?
+            .listen()?,
+    ))
+}
+
+// Wait for backlights to be added, removed or changed
+async fn backlights_changed(monitor: Option<&AsyncFd<WrappedSocket>>) {
+    let Some(monitor) = monitor else {
+        return iced::futures::future::pending().await;
+    };
+    loop {
+        let Ok(mut guard) = monitor.readable().await else {
+            return iced::futures::future::pending().await;
+        };
+        let changed = monitor.get_ref().0.iter().count() > 0;
+        guard.clear_ready_matching(tokio::io::Ready::READABLE);
+        if changed {
+            return;
+        }
+    }
 }
 
 pub fn screen_backlight_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
@@ -89,9 +172,10 @@ pub fn screen_backlight_subscription<I: 'static + Hash + Copy + Send + Sync + De
 pub enum State {
     Ready,
     Waiting(
-        Backlight,
+        Vec<Backlight>,
         LogindSessionProxy<'static>,
         UnboundedReceiver<ScreenBacklightRequest>,
+        Option<AsyncFd<WrappedSocket>>,
     ),
     Finished,
 }
@@ -110,53 +194,67 @@ async fn start_listening(
                 Ok(p) => p,
                 Err(_) => return State::Finished,
             };
-            let backlight = match backlight().await {
-                Ok(Some(b)) => b,
-                _ => return State::Finished,
+            let monitor = match backlight_monitor() {
+                Ok(monitor) => Some(monitor),
+                Err(err) => {
+                    tracing::warn!(?err, "Failed to monitor backlights");
+                    None
+                }
             };
+            let backlights = all_backlights().await.unwrap_or_default();
+            // Without a monitor, there is no way to notice backlights appearing later
+            if backlights.is_empty() && monitor.is_none() {
+                return State::Finished;
+            }
             let (tx, rx) = unbounded_channel();
 
-            let b = (backlight.brightness().await.unwrap_or_default() as f64
-                / backlight.max_brightness().await.unwrap_or(1) as f64)
-                .clamp(0., 1.);
-            _ = output.send(ScreenBacklightUpdate::Init(tx, b)).await;
+            _ = output
+                .send(ScreenBacklightUpdate::Init(tx, levels(&backlights).await))
+                .await;
 
-            State::Waiting(backlight, screen_proxy, rx)
+            State::Waiting(backlights, screen_proxy, rx, monitor)
         }
-        State::Waiting(backlight, proxy, mut rx) => match rx.recv().await {
-            Some(req) => match req {
-                ScreenBacklightRequest::Get => {
-                    if let Some(max_brightness) = backlight.max_brightness().await {
-                        let value = (backlight.brightness().await.unwrap_or_default() as f64
-                            / max_brightness as f64)
-                            .clamp(0., 1.);
-                        _ = output.send(ScreenBacklightUpdate::Update(value)).await;
-                    }
-                    State::Waiting(backlight, proxy, rx)
+        State::Waiting(mut backlights, proxy, mut rx, monitor) => {
+            let req = tokio::select! {
+                req = rx.recv() => req,
+                () = backlights_changed(monitor.as_ref()) => {
+                    backlights = all_backlights().await.unwrap_or_default();
+                    Some(ScreenBacklightRequest::Get)
                 }
-                ScreenBacklightRequest::Set(value) => {
-                    if let Some(max_brightness) = backlight.max_brightness().await {
-                        let value = value.clamp(0., 1.) * (max_brightness as f64);
-                        let value = value.round() as u32;
-                        let _ = backlight.set_brightness(&proxy, value).await;
+            };
+            match req {
+                Some(ScreenBacklightRequest::Get) => {
+                    _ = output
+                        .send(ScreenBacklightUpdate::Update(levels(&backlights).await))
+                        .await;
+                    State::Waiting(backlights, proxy, rx, monitor)
+                }
+                Some(ScreenBacklightRequest::Set(name, value)) => {
+                    if let Some(backlight) = backlights.iter().find(|b| b.name == name) {
+                        if let Some(max_brightness) = backlight.max_brightness().await {
+                            let value = value.clamp(0., 1.) * (max_brightness as f64);
+                            let value = value.round() as u32;
+                            let _ = backlight.set_brightness(&proxy, value).await;
+                        }
                     }
-                    State::Waiting(backlight, proxy, rx)
+                    State::Waiting(backlights, proxy, rx, monitor)
                 }
-            },
-            None => State::Finished,
-        },
+                None => State::Finished,
+            }
+        }
         State::Finished => iced::futures::future::pending().await,
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum ScreenBacklightUpdate {
-    Update(f64),
-    Init(UnboundedSender<ScreenBacklightRequest>, f64),
+    Update(Vec<BacklightLevel>),
+    Init(UnboundedSender<ScreenBacklightRequest>, Vec<BacklightLevel>),
 }
 
 #[derive(Debug, Clone)]
 pub enum ScreenBacklightRequest {
     Get,
-    Set(f64),
+    /// Set the backlight with the given name
+    Set(String, f64),
 }
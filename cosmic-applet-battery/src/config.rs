@@ -1 +1,50 @@
+use cosmic::cosmic_config::cosmic_config_derive::CosmicConfigEntry;
+use cosmic::cosmic_config::{self, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Power;
+use crate::profile_rules::{Condition, ProfileRule};
+
 pub const APP_ID: &str = "com.system76.CosmicAppletButton";
+
+/// What to do when the battery reaches the critical threshold.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum CriticalAction {
+    Nothing,
+    #[default]
+    Suspend,
+    Hibernate,
+    PowerOff,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, CosmicConfigEntry)]
+#[version = 1]
+pub struct BatteryAppletConfig {
+    pub peripheral_low_battery_notification: bool,
+    /// Percentage at which to warn that the battery is low
+    pub low_battery_threshold: u8,
+    /// Percentage at which to warn again and run `critical_battery_action`
+    pub critical_battery_threshold: u8,
+    pub critical_battery_action: CriticalAction,
+    pub snooze_minutes: u32,
+    pub profile_rules_enabled: bool,
+    pub profile_rules: Vec<ProfileRule>,
+}
+
+impl Default for BatteryAppletConfig {
+    fn default() -> Self {
+        Self {
+            peripheral_low_battery_notification: false,
+            low_battery_threshold: 10,
+            critical_battery_threshold: 5,
+            critical_battery_action: CriticalAction::default(),
+            snooze_minutes: 10,
+            profile_rules_enabled: false,
+            profile_rules: vec![
+                ProfileRule::new(Condition::OnAc, Power::Balanced),
+                ProfileRule::new(Condition::OnBattery, Power::Balanced),
+                ProfileRule::new(Condition::BatteryBelow(20), Power::Battery),
+            ],
+        }
+    }
+}
@@ -8,6 +8,7 @@ use std::{
     time::Duration,
 };
 
+use crate::drm_clients::ProcScanner;
 use cosmic::iced::{self, subscription};
 use drm::control::Device as ControlDevice;
 use futures::{FutureExt, SinkExt};
@@ -26,7 +27,7 @@ pub struct GpuMonitor {
     seat: String,
 }
 
-struct WrappedSocket(udev::MonitorSocket);
+pub(crate) struct WrappedSocket(pub(crate) udev::MonitorSocket);
 impl AsRawFd for WrappedSocket {
     fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
         self.0.as_raw_fd()
@@ -180,118 +181,82 @@ impl Gpu {
     }
 
     async fn app_list(&self, running_apps: &[RunningApp]) -> Option<Vec<Entry>> {
-        match self.driver.as_ref().and_then(|s| s.to_str()) {
-            Some("nvidia") => {
-                // figure out bus path for calling nvidia-smi
-                let mut sys_path = PathBuf::from("/sys/class/drm");
-                sys_path.push(self.path.components().last()?.as_os_str());
-                let buslink = std::fs::read_link(sys_path)
-                    .ok()?
-                    .components()
-                    .rev()
-                    .nth(2)?
-                    .as_os_str()
-                    .to_string_lossy()
-                    .into_owned();
-
-                let smi_output = match tokio::process::Command::new("nvidia-smi")
-                    .args(["pmon", "--id", &buslink, "--count", "1"])
-                    .output()
-                    .await
-                {
-                    Ok(output) if output.status.success() => {
-                        String::from_utf8_lossy(&output.stdout).into_owned()
-                    }
-                    Ok(output) => {
-                        debug!(
-                            "smi returned error code {}: {}",
-                            output.status,
-                            String::from_utf8_lossy(&output.stdout)
-                        );
-                        return None;
-                    }
-                    Err(err) => {
-                        debug!("smi returned error code: {}", err);
-                        return None;
-                    }
-                };
-
-                Some(
-                    smi_output
-                        .lines()
-                        .filter(|line| !line.starts_with('#'))
-                        .map(|line| {
-                            let components = line.split_whitespace().collect::<Vec<_>>();
-                            let pid = components[1].trim();
-                            let process_name = components.last().unwrap().trim();
-
-                            if let Some(application) = running_apps
-                                .iter()
-                                .find(|running_app| running_app.executable_name == process_name)
-                            {
-                                Entry {
-                                    name: application.name.clone(),
-                                    icon: application.icon.clone(),
-                                    secondary: String::new(),
-                                }
-                            } else {
-                                Entry {
-                                    name: process_name.to_string(),
-                                    icon: None,
-                                    secondary: pid.to_string(),
-                                }
-                            }
-                        })
-                        .collect(),
-                )
+        let path = self.path.clone();
+        let nvidia = self.driver.as_deref() == Some(OsStr::new("nvidia"));
+        let clients = spawn_blocking(move || {
+            let scanner = ProcScanner::default();
+            let pci_slot = pci_slot(&path);
+            let mut nodes = drm_nodes(&path);
+            if nvidia {
+                nodes.extend(
+                    pci_slot
+                        .as_deref()
+                        .and_then(|pci_slot| scanner.nvidia_node(pci_slot)),
+                );
             }
-            _ => {
-                let lsof_output = match tokio::process::Command::new("lsof")
-                    .args([OsStr::new("-t"), self.path.as_os_str()])
-                    .output()
-                    .await
-                {
-                    Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
-                    Err(err) => {
-                        debug!("lsof returned error code: {}", err);
-                        return None;
+            scanner.clients(&nodes, pci_slot.as_deref())
+        })
+        .await
+        .ok()?;
+        let clients = match clients {
+            Ok(clients) => clients,
+            Err(err) => {
+                debug!("failed to scan processes: {}", err);
+                return None;
+            }
+        };
+
+        Some(
+            clients
+                .into_iter()
+                .map(|client| {
+                    if let Some(application) = running_apps
+                        .iter()
+                        .find(|running_app| running_app.executable_name == client.executable)
+                    {
+                        Entry {
+                            name: application.name.clone(),
+                            icon: application.icon.clone(),
+                            secondary: String::new(),
+                        }
+                    } else {
+                        Entry {
+                            name: client.executable,
+                            icon: None,
+                            secondary: client.pid.to_string(),
+                        }
                     }
-                };
-
-                Some(
-                    lsof_output
-                        .lines()
-                        .filter_map(|pid| {
-                            let executable = std::fs::read_link(format!("/proc/{}/exe", pid))
-                                .ok()?
-                                .components()
-                                .last()?
-                                .as_os_str()
-                                .to_string_lossy()
-                                .into_owned();
-
-                            if let Some(application) = running_apps
-                                .iter()
-                                .find(|running_app| running_app.executable_name == executable)
-                            {
-                                Some(Entry {
-                                    name: application.name.clone(),
-                                    icon: application.icon.clone(),
-                                    secondary: String::new(),
-                                })
-                            } else {
-                                Some(Entry {
-                                    name: executable,
-                                    icon: None,
-                                    secondary: pid.to_string(),
-                                })
-                            }
-                        })
-                        .collect(),
-                )
+                })
+                .collect(),
+        )
+    }
+}
+
+fn sysfs_drm_path(node: &Path) -> Option<PathBuf> {
+    Some(Path::new("/sys/class/drm").join(node.file_name()?))
+}
+
+// The PCI address of the GPU, as reported in `drm-pdev`
+fn pci_slot(node: &Path) -> Option<String> {
+    let device = std::fs::canonicalize(sysfs_drm_path(node)?.join("device")).ok()?;
+    Some(device.file_name()?.to_string_lossy().into_owned())
+}
+
+// The card and render nodes of the GPU
+fn drm_nodes(node: &Path) -> Vec<PathBuf> {
+    let mut nodes = vec![node.to_path_buf()];
+    let Some(drm_dir) = sysfs_drm_path(node).map(|path| path.join("device/drm")) else {
+        return nodes;
+    };
+    if let Ok(entries) = std::fs::read_dir(drm_dir) {
+        for entry in entries.flatten() {
+            let path = Path::new("/dev/dri").join(entry.file_name());
+            if !nodes.contains(&path) {
+                nodes.push(path);
             }
         }
     }
+    nodes
 }
 
 fn all_gpus<S: AsRef<str>>(seat: S) -> io::Result<Vec<Gpu>> {
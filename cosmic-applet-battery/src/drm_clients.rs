@@ -0,0 +1,297 @@
+//! Find the processes using a GPU by scanning `/proc/<pid>/fd` and `/proc/<pid>/fdinfo`.
+//!
+//! See `Documentation/gpu/drm-usage-stats.rst` in the kernel tree for the DRM `fdinfo` keys.
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrmClient {
+    pub pid: u32,
+    pub executable: String,
+    /// Nanoseconds the GPU spent on the process' work, if the driver reports it
+    pub engine_time: Option<u64>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct FdInfo<'a> {
+    pdev: Option<&'a str>,
+    client_id: Option<&'a str>,
+    engine_time: Option<u64>,
+}
+
+impl<'a> FdInfo<'a> {
+    fn parse(fdinfo: &'a str) -> Self {
+        let mut info = Self::default();
+        for (key, value) in fdinfo
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim(), value.trim()))
+        {
+            match key {
+                "drm-pdev" => info.pdev = Some(value),
+                "drm-client-id" => info.client_id = Some(value),
+                // `drm-engine-<name>: <ns> ns`, but not `drm-engine-capacity-<name>`
+                _ if key.starts_with("drm-engine-") && !key.starts_with("drm-engine-capacity-") => {
+                    if let Some(ns) = value
+                        .split_whitespace()
+                        .next()
+                        .and_then(|ns| ns.parse::<u64>().ok())
+                    {
+                        info.engine_time = Some(info.engine_time.unwrap_or_default() + ns);
+                    }
+                }
+                _ => {}
+            }
+        }
+        info
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcScanner {
+    root: PathBuf,
+}
+
+impl Default for ProcScanner {
+    fn default() -> Self {
+        Self::new("/proc")
+    }
+}
+
+impl ProcScanner {
+    /// `root` is the `proc` mount, so a fixture directory can be used instead of `/proc`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The `/dev/nvidia<minor>` node of the NVIDIA GPU at `pci_slot`, which the proprietary
+    /// driver uses instead of DRM nodes.
+    pub fn nvidia_node(&self, pci_slot: &str) -> Option<PathBuf> {
+        let information = fs::read_to_string(
+            self.root
+                .join("driver/nvidia/gpus")
+                .join(pci_slot)
+                .join("information"),
+        )
+        .ok()?;
+        let minor = information
+            .lines()
+            .find_map(|line| line.strip_prefix("Device Minor:"))?
+            .trim();
+        Some(PathBuf::from(format!("/dev/nvidia{minor}")))
+    }
+
+    /// Processes with a file descriptor open on one of `nodes`. DRM clients reporting
+    /// a `drm-pdev` other than `pci_slot` are skipped. The busiest processes come first.
+    pub fn clients(&self, nodes: &[PathBuf], pci_slot: Option<&str>) -> io::Result<Vec<DrmClient>> {
+        let mut clients = Vec::new();
+        for entry in fs::read_dir(&self.root)?.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            if let Some(client) = client(pid, &entry.path(), nodes, pci_slot) {
+                clients.push(client);
+            }
+        }
+        clients.sort_by(|a, b| b.engine_time.cmp(&a.engine_time).then(a.pid.cmp(&b.pid)));
+        Ok(clients)
+    }
+}
+
+fn client(pid: u32, dir: &Path, nodes: &[PathBuf], pci_slot: Option<&str>) -> Option<DrmClient> {
+    // Fails for processes of other users, which we can't inspect anyway
+    let fds = fs::read_dir(dir.join("fd")).ok()?;
+
+    let mut uses_gpu = false;
+    let mut engine_time = None;
+    // Several file descriptors can share one DRM client, count its engine time once
+    let mut client_ids = HashSet::new();
+    for fd in fds.flatten() {
+        let Ok(target) = fs::read_link(fd.path()) else {
+            continue;
+        };
+        if !nodes.contains(&target) {
+            continue;
+        }
+
+        let fdinfo =
+            fs::read_to_string(dir.join("fdinfo").join(fd.file_name())).unwrap_or_default();
+        let info = FdInfo::parse(&fdinfo);
+        if let (Some(pci_slot), Some(pdev)) = (pci_slot, info.pdev) {
+            if pci_slot != pdev {
+                continue;
+            }
+        }
+        uses_gpu = true;
+        let counted = match info.client_id {
+            Some(id) => !client_ids.insert(id.to_owned()),
+            None => false,
+        };
+        if let Some(time) = info.engine_time.filter(|_| !counted) {
+            engine_time = Some(engine_time.unwrap_or_default() + time);
+        }
+    }
+    if !uses_gpu {
+        return None;
+    }
+
+    let executable = fs::read_link(dir.join("exe"))
+        .ok()
+        .and_then(|exe| Some(exe.file_name()?.to_string_lossy().into_owned()))
+        .or_else(|| Some(fs::read_to_string(dir.join("comm")).ok()?.trim().to_owned()))?;
+    Some(DrmClient {
+        pid,
+        executable,
+        engine_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    const NODE: &str = "/dev/dri/renderD128";
+    const SLOT: &str = "0000:01:00.0";
+
+    // Add an open file descriptor on `target` to the process `pid`
+    fn add_fd(root: &Path, pid: u32, fd: u32, target: &str, fdinfo: &str) {
+        let dir = root.join(pid.to_string());
+        fs::create_dir_all(dir.join("fd")).unwrap();
+        fs::create_dir_all(dir.join("fdinfo")).unwrap();
+        symlink(target, dir.join("fd").join(fd.to_string())).unwrap();
+        fs::write(dir.join("fdinfo").join(fd.to_string()), fdinfo).unwrap();
+    }
+
+    fn fixture() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path();
+
+        // Two file descriptors of one DRM client, and a second client
+        add_fd(
+            path,
+            100,
+            3,
+            NODE,
+            "drm-pdev:\t0000:01:00.0\ndrm-client-id:\t7\ndrm-engine-gfx:\t1000 ns\n\
+             drm-engine-capacity-gfx:\t2\n",
+        );
+        add_fd(
+            path,
+            100,
+            4,
+            NODE,
+            "drm-pdev:\t0000:01:00.0\ndrm-client-id:\t7\ndrm-engine-gfx:\t1000 ns\n",
+        );
+        add_fd(
+            path,
+            100,
+            5,
+            NODE,
+            "drm-pdev:\t0000:01:00.0\ndrm-client-id:\t8\ndrm-engine-gfx:\t500 ns\n\
+             drm-engine-compute:\t250 ns\n",
+        );
+        symlink("/usr/bin/game", path.join("100/exe")).unwrap();
+
+        // No engine time and no readable `exe`
+        add_fd(path, 200, 0, NODE, "drm-pdev:\t0000:01:00.0\n");
+        fs::write(path.join("200/comm"), "compositor\n").unwrap();
+
+        // Another GPU behind the same node
+        add_fd(
+            path,
+            300,
+            3,
+            NODE,
+            "drm-pdev:\t0000:02:00.0\ndrm-engine-gfx:\t10 ns\n",
+        );
+        fs::write(path.join("300/comm"), "other\n").unwrap();
+
+        // Not a GPU user
+        add_fd(path, 400, 0, "/dev/null", "");
+        fs::write(path.join("400/comm"), "shell\n").unwrap();
+
+        fs::create_dir(path.join("self")).unwrap();
+        root
+    }
+
+    #[test]
+    fn parse_fdinfo() {
+        let info = FdInfo::parse(
+            "pos:\t0\ndrm-pdev:\t0000:01:00.0\ndrm-client-id:\t7\n\
+             drm-engine-gfx:\t100 ns\ndrm-engine-capacity-gfx:\t4\ndrm-engine-video:\t20 ns\n",
+        );
+        assert_eq!(
+            info,
+            FdInfo {
+                pdev: Some(SLOT),
+                client_id: Some("7"),
+                engine_time: Some(120),
+            }
+        );
+        assert_eq!(FdInfo::parse(""), FdInfo::default());
+    }
+
+    #[test]
+    fn clients_of_gpu() {
+        let root = fixture();
+        let scanner = ProcScanner::new(root.path());
+        let clients = scanner.clients(&[PathBuf::from(NODE)], Some(SLOT)).unwrap();
+        assert_eq!(
+            clients,
+            [
+                DrmClient {
+                    pid: 100,
+                    executable: "game".to_owned(),
+                    engine_time: Some(1750),
+                },
+                DrmClient {
+                    pid: 200,
+                    executable: "compositor".to_owned(),
+                    engine_time: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn clients_without_pci_slot() {
+        let root = fixture();
+        let scanner = ProcScanner::new(root.path());
+        let pids: Vec<u32> = scanner
+            .clients(&[PathBuf::from(NODE)], None)
+            .unwrap()
+            .iter()
+            .map(|client| client.pid)
+            .collect();
+        assert_eq!(pids, [100, 300, 200]);
+    }
+
+    #[test]
+    fn nvidia_node() {
+        let root = tempfile::tempdir().unwrap();
+        let gpu = root.path().join("driver/nvidia/gpus").join(SLOT);
+        fs::create_dir_all(&gpu).unwrap();
+        fs::write(
+            gpu.join("information"),
+            "Model: \t\t NVIDIA GeForce RTX 3060\nIRQ:   \t\t 150\nDevice Minor: \t 1\n",
+        )
+        .unwrap();
+
+        let scanner = ProcScanner::new(root.path());
+        assert_eq!(
+            scanner.nvidia_node(SLOT),
+            Some(PathBuf::from("/dev/nvidia1"))
+        );
+        assert_eq!(scanner.nvidia_node("0000:02:00.0"), None);
+    }
+}
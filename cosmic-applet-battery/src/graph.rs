@@ -0,0 +1,193 @@
+use std::ops::RangeInclusive;
+
+use cosmic::iced_core::{
+    layout, mouse, renderer, widget::Tree, Border, Color, Element, Layout, Length, Rectangle,
+    Renderer as _, Shadow, Size, Widget,
+};
+
+/// A bar graph of `(x, y)` samples. Samples are averaged into a fixed number of bars
+/// spanning `x_range`, and bars without samples are left empty.
+pub struct Graph {
+    points: Vec<(f64, f64)>,
+    x_range: RangeInclusive<f64>,
+    y_range: RangeInclusive<f64>,
+    bars: usize,
+    width: Length,
+    height: Length,
+}
+
+impl Graph {
+    pub fn new(points: Vec<(f64, f64)>) -> Self {
+        let x_min = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+        let x_max = points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+        let y_max = points.iter().map(|p| p.1).fold(0.0, f64::max);
+        Self {
+            points,
+            x_range: x_min..=x_max,
+            y_range: 0.0..=y_max,
+            bars: 48,
+            width: Length::Fill,
+            height: Length::Fixed(48.0),
+        }
+    }
+
+    pub fn x_range(mut self, x_range: RangeInclusive<f64>) -> Self {
+        self.x_range = x_range;
+        self
+    }
+
+    pub fn y_range(mut self, y_range: RangeInclusive<f64>) -> Self {
+        self.y_range = y_range;
+        self
+    }
+
+    pub fn bars(mut self, bars: usize) -> Self {
+        self.bars = bars;
+        self
+    }
+
+    pub fn height(mut self, height: impl Into<Length>) -> Self {
+        self.height = height.into();
+        self
+    }
+
+    // Normalized (0.0 - 1.0) height of each bar, `None` for bars without samples
+    fn bar_values(&self) -> Vec<Option<f32>> {
+        let (x_min, x_max) = (*self.x_range.start(), *self.x_range.end());
+        let (y_min, y_max) = (*self.y_range.start(), *self.y_range.end());
+        if self.bars == 0 || x_max <= x_min || y_max <= y_min {
+            return Vec::new();
+        }
+
+        let mut buckets = vec![(0.0, 0usize); self.bars];
+        for &(x, y) in &self.points {
+            if x < x_min || x > x_max {
+                continue;
+            }
+            let i = ((x - x_min) / (x_max - x_min) * self.bars as f64) as usize;
+            let bucket = &mut buckets[i.min(self.bars - 1)];
+            bucket.0 += y;
+            bucket.1 += 1;
+        }
+
+        buckets
+            .into_iter()
+            .map(|(sum, count)| {
+                (count > 0).then(|| {
+                    ((sum / count as f64 - y_min) / (y_max - y_min)).clamp(0.0, 1.0) as f32
+                })
+            })
+            .collect()
+    }
+}
+
+impl<Message> Widget<Message, cosmic::Theme, cosmic::Renderer> for Graph {
+    fn size(&self) -> Size<Length> {
+        Size::new(self.width, self.height)
+    }
+
+    fn layout(
+        &self,
+        _tree: &mut Tree,
+        _renderer: &cosmic::Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        layout::atomic(limits, self.width, self.height)
+    }
+
+    fn draw(
+        &self,
+        _tree: &Tree,
+        renderer: &mut cosmic::Renderer,
+        theme: &cosmic::Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let color: Color = theme.cosmic().accent_color().into();
+        let track = Color { a: 0.1, ..color };
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border: Border {
+                    radius: 4.0.into(),
+                    width: 0.0,
+                    color: Color::TRANSPARENT,
+                },
+                shadow: Shadow::default(),
+            },
+            track,
+        );
+
+        let bars = self.bar_values();
+        let bar_width = bounds.width / bars.len().max(1) as f32;
+        for (i, value) in bars.into_iter().enumerate() {
+            let Some(value) = value else {
+                continue;
+            };
+            let height = (value * bounds.height).max(1.0);
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x: bounds.x + i as f32 * bar_width,
+                        y: bounds.y + bounds.height - height,
+                        width: (bar_width - 1.0).max(1.0),
+                        height,
+                    },
+                    border: Border::default(),
+                    shadow: Shadow::default(),
+                },
+                color,
+            );
+        }
+    }
+}
+
+impl<'a, Message: 'a> From<Graph> for Element<'a, Message, cosmic::Theme, cosmic::Renderer> {
+    fn from(graph: Graph) -> Self {
+        Self::new(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_samples_per_bar() {
+        let graph = Graph::new(vec![(0.0, 10.0), (1.0, 30.0), (9.0, 50.0), (10.0, 100.0)])
+            .y_range(0.0..=100.0)
+            .bars(2);
+        // The last sample sits on the end of `x_range`, and goes in the last bar
+        assert_eq!(graph.bar_values(), [Some(0.2), Some(0.75)]);
+    }
+
+    #[test]
+    fn bars_without_samples_are_empty() {
+        let graph = Graph::new(vec![(-1.0, 50.0), (3.0, 50.0), (11.0, 50.0)])
+            .x_range(0.0..=10.0)
+            .y_range(0.0..=100.0)
+            .bars(4);
+        assert_eq!(graph.bar_values(), [None, Some(0.5), None, None]);
+    }
+
+    #[test]
+    fn values_are_clamped_to_y_range() {
+        let graph = Graph::new(vec![(0.0, 25.0), (10.0, 150.0)])
+            .y_range(50.0..=100.0)
+            .bars(2);
+        assert_eq!(graph.bar_values(), [Some(0.0), Some(1.0)]);
+    }
+
+    #[test]
+    fn empty_ranges() {
+        assert!(Graph::new(Vec::new()).bar_values().is_empty());
+        let graph = || Graph::new(vec![(0.0, 1.0), (10.0, 2.0)]);
+        assert!(graph().x_range(5.0..=5.0).bar_values().is_empty());
+        assert!(graph().y_range(1.0..=1.0).bar_values().is_empty());
+        assert!(graph().bars(0).bar_values().is_empty());
+    }
+}
@@ -4,10 +4,15 @@ mod app;
 mod backend;
 mod config;
 mod dgpu;
+mod drm_clients;
+mod graph;
 mod localize;
+mod notifications;
+mod profile_rules;
 mod upower;
 mod upower_device;
 mod upower_kbdbacklight;
+mod warning;
 
 use localize::localize;
 
@@ -0,0 +1,47 @@
+//! # DBus interface proxy for: `org.freedesktop.Notifications`
+//!
+//! This code was generated by `zbus-xmlgen` `3.1.1` from DBus introspection data.
+//! Source: `Interface '/org/freedesktop/Notifications' from service 'org.freedesktop.Notifications' on session bus`.
+
+use std::collections::HashMap;
+use zbus::dbus_proxy;
+
+#[dbus_proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    /// CloseNotification method
+    fn close_notification(&self, id: u32) -> zbus::Result<()>;
+
+    /// Notify method
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: std::collections::HashMap<&str, zbus::zvariant::Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+pub async fn notify(summary: &str, body: &str, icon: &str) -> zbus::Result<u32> {
+    let conn = zbus::Connection::session().await?;
+    let proxy = NotificationsProxy::new(&conn).await?;
+    proxy
+        .notify(
+            "cosmic-applet-battery",
+            0,
+            icon,
+            summary,
+            body,
+            &[],
+            HashMap::new(),
+            -1,
+        )
+        .await
+}
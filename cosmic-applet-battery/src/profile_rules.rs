@@ -0,0 +1,188 @@
+//! Rules for switching the power profile when the power source or battery percentage changes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Power;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Condition {
+    OnBattery,
+    OnAc,
+    /// On battery at or below the percentage
+    BatteryBelow(u8),
+}
+
+impl Condition {
+    pub fn matches(self, on_battery: bool, percent: f64) -> bool {
+        match self {
+            Self::OnBattery => on_battery,
+            Self::OnAc => !on_battery,
+            Self::BatteryBelow(threshold) => on_battery && percent <= f64::from(threshold),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ProfileRule {
+    pub condition: Condition,
+    pub profile: Power,
+}
+
+impl ProfileRule {
+    pub const fn new(condition: Condition, profile: Power) -> Self {
+        Self { condition, profile }
+    }
+}
+
+/// The profile asked for by the last matching rule, so more specific rules go last.
+pub fn rule_profile(rules: &[ProfileRule], on_battery: bool, percent: f64) -> Option<Power> {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.condition.matches(on_battery, percent))
+        .map(|rule| rule.profile)
+}
+
+/// Tracks which profile the rules ask for, so a profile is only applied when that changes
+/// and a profile picked by hand sticks until the power source changes.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileSwitcher {
+    target: Option<Power>,
+    on_battery: Option<bool>,
+    overridden: bool,
+    /// The last profile picked by hand, whether or not rules are enabled.
+    selected: Option<Power>,
+}
+
+impl ProfileSwitcher {
+    /// Returns the profile to switch to, if any.
+    pub fn update(
+        &mut self,
+        rules: &[ProfileRule],
+        on_battery: bool,
+        percent: f64,
+    ) -> Option<Power> {
+        if self.on_battery.replace(on_battery) != Some(on_battery) {
+            self.overridden = false;
+        }
+        let target = rule_profile(rules, on_battery, percent);
+        let changed = target != self.target;
+        self.target = target;
+        if changed && !self.overridden {
+            target
+        } else {
+            None
+        }
+    }
+
+    /// Stop applying rules until the power source changes.
+    pub fn override_rules(&mut self) {
+        self.overridden = true;
+    }
+
+    /// Remember a profile picked by hand, to go back to after holds are released.
+    pub fn select(&mut self, profile: Power) {
+        self.selected = Some(profile);
+    }
+
+    /// The profile to go back to once holds are released: the one the rules ask for, unless
+    /// a profile was picked by hand since.
+    pub fn restore_profile(&self) -> Option<Power> {
+        if self.overridden {
+            self.selected
+        } else {
+            self.target.or(self.selected)
+        }
+    }
+
+    /// Apply rules again, returning the profile they currently ask for.
+    pub fn resume(&mut self) -> Option<Power> {
+        self.overridden = false;
+        self.target
+    }
+
+    /// Forget the current target, so the next update applies the matching rule.
+    pub fn reset(&mut self) {
+        *self = Self {
+            selected: self.selected,
+            ..Self::default()
+        };
+    }
+
+    pub fn is_overridden(&self) -> bool {
+        self.overridden
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: [ProfileRule; 3] = [
+        ProfileRule::new(Condition::OnAc, Power::Performance),
+        ProfileRule::new(Condition::OnBattery, Power::Balanced),
+        ProfileRule::new(Condition::BatteryBelow(20), Power::Battery),
+    ];
+
+    #[test]
+    fn last_matching_rule_wins() {
+        assert_eq!(rule_profile(&RULES, true, 50.), Some(Power::Balanced));
+        assert_eq!(rule_profile(&RULES, true, 20.), Some(Power::Battery));
+        assert_eq!(rule_profile(&RULES, false, 50.), Some(Power::Performance));
+        assert_eq!(rule_profile(&[], true, 50.), None);
+
+        let reversed = [RULES[2], RULES[1]];
+        assert_eq!(rule_profile(&reversed, true, 10.), Some(Power::Balanced));
+    }
+
+    #[test]
+    fn battery_below_only_on_battery() {
+        let rules = [ProfileRule::new(
+            Condition::BatteryBelow(20),
+            Power::Battery,
+        )];
+        assert_eq!(rule_profile(&rules, true, 10.), Some(Power::Battery));
+        assert_eq!(rule_profile(&rules, false, 10.), None);
+        assert_eq!(rule_profile(&rules, true, 21.), None);
+    }
+
+    #[test]
+    fn update_only_applies_changes() {
+        let mut switcher = ProfileSwitcher::default();
+        assert_eq!(switcher.update(&RULES, true, 50.), Some(Power::Balanced));
+        assert_eq!(switcher.update(&RULES, true, 40.), None);
+        assert_eq!(switcher.update(&RULES, true, 15.), Some(Power::Battery));
+        assert_eq!(switcher.update(&RULES, true, 14.), None);
+        assert_eq!(
+            switcher.update(&RULES, false, 14.),
+            Some(Power::Performance)
+        );
+    }
+
+    #[test]
+    fn override_sticks_until_power_source_changes() {
+        let mut switcher = ProfileSwitcher::default();
+        switcher.update(&RULES, true, 50.);
+        switcher.override_rules();
+        assert_eq!(switcher.update(&RULES, true, 15.), None);
+        assert!(switcher.is_overridden());
+
+        assert_eq!(
+            switcher.update(&RULES, false, 15.),
+            Some(Power::Performance)
+        );
+        assert!(!switcher.is_overridden());
+    }
+
+    #[test]
+    fn resume_returns_current_target() {
+        let mut switcher = ProfileSwitcher::default();
+        switcher.update(&RULES, true, 50.);
+        switcher.override_rules();
+        switcher.update(&RULES, true, 15.);
+
+        assert_eq!(switcher.resume(), Some(Power::Battery));
+        assert!(!switcher.is_overridden());
+        assert_eq!(switcher.update(&RULES, true, 14.), None);
+    }
+}
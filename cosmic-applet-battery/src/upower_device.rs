@@ -5,12 +5,12 @@
 
 use cosmic::iced::{
     self,
-    futures::{SinkExt, StreamExt},
+    futures::{stream::BoxStream, SinkExt, StreamExt},
     subscription,
 };
 
 use std::{fmt::Debug, hash::Hash};
-use zbus::dbus_proxy;
+use zbus::{dbus_proxy, zvariant::OwnedObjectPath};
 
 use crate::upower::UPowerProxy;
 #[dbus_proxy(
@@ -168,7 +168,11 @@ pub fn device_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
 #[derive(Debug)]
 pub enum State {
     Ready,
-    Waiting(UPowerProxy<'static>, DeviceProxy<'static>),
+    Waiting(
+        UPowerProxy<'static>,
+        DeviceProxy<'static>,
+        Option<DeviceProxy<'static>>,
+    ),
     Finished,
 }
 
@@ -184,6 +188,49 @@ async fn display_device() -> zbus::Result<(UPowerProxy<'static>, DeviceProxy<'st
         .map(|dp| (upower, dp))
 }
 
+// The display device is a composite of all batteries, without details like health or history,
+// so those come from the first battery powering the system.
+async fn system_battery(
+    connection: &zbus::Connection,
+) -> zbus::Result<Option<DeviceProxy<'static>>> {
+    let upower = UPowerProxy::new(connection).await?;
+    for path in upower.enumerate_devices().await? {
+        let device = DeviceProxy::builder(connection)
+            .path(path)?
+            .cache_properties(zbus::CacheProperties::Yes)
+            .build()
+            .await?;
+        if DeviceKind::from(cached(device.cached_type_())) == DeviceKind::Battery
+            && cached(device.cached_power_supply())
+        {
+            return Ok(Some(device));
+        }
+    }
+    Ok(None)
+}
+
+fn display_update(upower: &UPowerProxy<'_>, device: &DeviceProxy<'_>) -> DeviceDbusEvent {
+    DeviceDbusEvent::Update {
+        on_battery: upower
+            .cached_on_battery()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        percent: device
+            .cached_percentage()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        time_to_empty: device
+            .cached_time_to_empty()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        time_to_full: device
+            .cached_time_to_full()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        state: DeviceState::from(cached(device.cached_state())),
+    }
+}
+
 async fn start_listening(
     state: State,
     output: &mut futures::channel::mpsc::Sender<DeviceDbusEvent>,
@@ -191,52 +238,55 @@ async fn start_listening(
     match state {
         State::Ready => {
             if let Ok((upower, device)) = display_device().await {
-                _ = output
-                    .send(DeviceDbusEvent::Update {
-                        on_battery: upower
-                            .cached_on_battery()
-                            .unwrap_or_default()
-                            .unwrap_or_default(),
-                        percent: device
-                            .cached_percentage()
-                            .unwrap_or_default()
-                            .unwrap_or_default(),
-                        time_to_empty: device
-                            .cached_time_to_empty()
-                            .unwrap_or_default()
-                            .unwrap_or_default(),
-                    })
-                    .await;
-                return State::Waiting(upower, device);
+                let battery = match system_battery(upower.connection()).await {
+                    Ok(battery) => battery,
+                    Err(err) => {
+                        tracing::warn!(?err, "Failed to find the system battery");
+                        None
+                    }
+                };
+                _ = output.send(display_update(&upower, &device)).await;
+                if let Some(battery) = battery.as_ref() {
+                    _ = output
+                        .send(DeviceDbusEvent::Details(BatteryDetails::from_device(
+                            battery,
+                        )))
+                        .await;
+                }
+                return State::Waiting(upower, device, battery);
             }
             State::Finished
         }
-        State::Waiting(upower, device) => {
+        State::Waiting(upower, device, battery) => {
+            // UPower bumps `UpdateTime` whenever it refreshes the battery, along with
+            // any other property that changed.
+            let details = match battery.as_ref() {
+                Some(battery) => battery
+                    .receive_update_time_changed()
+                    .await
+                    .map(|_| true)
+                    .boxed(),
+                None => futures::stream::pending().boxed(),
+            };
             let mut stream = futures::stream_select!(
-                upower.receive_on_battery_changed().await.map(|_| ()),
-                device.receive_percentage_changed().await.map(|_| ()),
-                device.receive_time_to_empty_changed().await.map(|_| ()),
+                upower.receive_on_battery_changed().await.map(|_| false),
+                device.receive_percentage_changed().await.map(|_| false),
+                device.receive_time_to_empty_changed().await.map(|_| false),
+                device.receive_time_to_full_changed().await.map(|_| false),
+                device.receive_state_changed().await.map(|_| false),
+                details,
             );
             match stream.next().await {
-                Some(_) => {
-                    _ = output
-                        .send(DeviceDbusEvent::Update {
-                            on_battery: upower
-                                .cached_on_battery()
-                                .unwrap_or_default()
-                                .unwrap_or_default(),
-                            percent: device
-                                .cached_percentage()
-                                .unwrap_or_default()
-                                .unwrap_or_default(),
-                            time_to_empty: device
-                                .cached_time_to_empty()
-                                .unwrap_or_default()
-                                .unwrap_or_default(),
-                        })
-                        .await;
-
-                    State::Waiting(upower, device)
+                Some(details_changed) => {
+                    let event = match battery.as_ref() {
+                        Some(battery) if details_changed => {
+                            DeviceDbusEvent::Details(BatteryDetails::from_device(battery))
+                        }
+                        _ => display_update(&upower, &device),
+                    };
+                    _ = output.send(event).await;
+
+                    State::Waiting(upower, device, battery)
                 }
                 None => State::Finished,
             }
@@ -251,5 +301,471 @@ pub enum DeviceDbusEvent {
         on_battery: bool,
         percent: f64,
         time_to_empty: i64,
+        time_to_full: i64,
+        state: DeviceState,
     },
+    Details(BatteryDetails),
+}
+
+/// `Technology` property of `org.freedesktop.UPower.Device`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Technology {
+    #[default]
+    Unknown,
+    LithiumIon,
+    LithiumPolymer,
+    LithiumIronPhosphate,
+    LeadAcid,
+    NickelCadmium,
+    NickelMetalHydride,
+}
+
+impl From<u32> for Technology {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Self::LithiumIon,
+            2 => Self::LithiumPolymer,
+            3 => Self::LithiumIronPhosphate,
+            4 => Self::LeadAcid,
+            5 => Self::NickelCadmium,
+            6 => Self::NickelMetalHydride,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Health and live readings of the battery powering the system.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatteryDetails {
+    /// Full capacity relative to the design capacity, in percent
+    pub capacity: f64,
+    /// `None` if the battery doesn't report it
+    pub charge_cycles: Option<u32>,
+    /// Wh
+    pub energy: f64,
+    /// Wh
+    pub energy_full: f64,
+    /// Wh
+    pub energy_full_design: f64,
+    /// W
+    pub energy_rate: f64,
+    /// V
+    pub voltage: f64,
+    /// °C, `None` if the battery doesn't report it
+    pub temperature: Option<f64>,
+    pub technology: Technology,
+    pub vendor: String,
+    pub model: String,
+    pub serial: String,
+}
+
+impl BatteryDetails {
+    fn from_device(device: &DeviceProxy<'_>) -> Self {
+        let charge_cycles: i32 = cached(device.cached_charge_cycles());
+        let temperature: f64 = cached(device.cached_temperature());
+        Self {
+            capacity: cached(device.cached_capacity()),
+            charge_cycles: u32::try_from(charge_cycles).ok().filter(|c| *c > 0),
+            energy: cached(device.cached_energy()),
+            energy_full: cached(device.cached_energy_full()),
+            energy_full_design: cached(device.cached_energy_full_design()),
+            energy_rate: cached(device.cached_energy_rate()),
+            voltage: cached(device.cached_voltage()),
+            temperature: (temperature != 0.0).then_some(temperature),
+            technology: Technology::from(cached(device.cached_technology())),
+            vendor: cached(device.cached_vendor()),
+            model: cached(device.cached_model()),
+            serial: cached(device.cached_serial()),
+        }
+    }
+}
+
+/// `Type` property of `org.freedesktop.UPower.Device`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceKind {
+    #[default]
+    Unknown,
+    LinePower,
+    Battery,
+    Ups,
+    Monitor,
+    Mouse,
+    Keyboard,
+    Pda,
+    Phone,
+    MediaPlayer,
+    Tablet,
+    Computer,
+    GamingInput,
+    Pen,
+    Touchpad,
+    Modem,
+    Network,
+    Headset,
+    Speakers,
+    Headphones,
+    Video,
+    OtherAudio,
+    RemoteControl,
+    Printer,
+    Scanner,
+    Camera,
+    Wearable,
+    Toy,
+    BluetoothGeneric,
+}
+
+impl From<u32> for DeviceKind {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Self::LinePower,
+            2 => Self::Battery,
+            3 => Self::Ups,
+            4 => Self::Monitor,
+            5 => Self::Mouse,
+            6 => Self::Keyboard,
+            7 => Self::Pda,
+            8 => Self::Phone,
+            9 => Self::MediaPlayer,
+            10 => Self::Tablet,
+            11 => Self::Computer,
+            12 => Self::GamingInput,
+            13 => Self::Pen,
+            14 => Self::Touchpad,
+            15 => Self::Modem,
+            16 => Self::Network,
+            17 => Self::Headset,
+            18 => Self::Speakers,
+            19 => Self::Headphones,
+            20 => Self::Video,
+            21 => Self::OtherAudio,
+            22 => Self::RemoteControl,
+            23 => Self::Printer,
+            24 => Self::Scanner,
+            25 => Self::Camera,
+            26 => Self::Wearable,
+            27 => Self::Toy,
+            28 => Self::BluetoothGeneric,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl DeviceKind {
+    pub fn icon_name(self) -> &'static str {
+        match self {
+            Self::Ups => "uninterruptible-power-supply-symbolic",
+            Self::Monitor | Self::Video => "video-display-symbolic",
+            Self::Mouse => "input-mouse-symbolic",
+            Self::Keyboard => "input-keyboard-symbolic",
+            Self::Pda | Self::Phone => "phone-symbolic",
+            Self::MediaPlayer => "multimedia-player-symbolic",
+            Self::Tablet | Self::Pen => "input-tablet-symbolic",
+            Self::Computer => "computer-symbolic",
+            Self::GamingInput => "input-gaming-symbolic",
+            Self::Touchpad => "input-touchpad-symbolic",
+            Self::Modem => "modem-symbolic",
+            Self::Network => "network-wired-symbolic",
+            Self::Headset => "audio-headset-symbolic",
+            Self::Speakers | Self::OtherAudio => "audio-speakers-symbolic",
+            Self::Headphones => "audio-headphones-symbolic",
+            Self::Printer => "printer-symbolic",
+            Self::Scanner => "scanner-symbolic",
+            Self::Camera => "camera-photo-symbolic",
+            Self::RemoteControl | Self::Wearable | Self::Toy | Self::BluetoothGeneric => {
+                "bluetooth-symbolic"
+            }
+            Self::Unknown | Self::LinePower | Self::Battery => "battery-symbolic",
+        }
+    }
+}
+
+/// `State` property of `org.freedesktop.UPower.Device`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceState {
+    #[default]
+    Unknown,
+    Charging,
+    Discharging,
+    Empty,
+    FullyCharged,
+    PendingCharge,
+    PendingDischarge,
+}
+
+impl From<u32> for DeviceState {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Self::Charging,
+            2 => Self::Discharging,
+            3 => Self::Empty,
+            4 => Self::FullyCharged,
+            5 => Self::PendingCharge,
+            6 => Self::PendingDischarge,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+// `WarningLevel` values from 3 (low) and up
+const WARNING_LEVEL_LOW: u32 = 3;
+
+/// A device with its own battery, like a mouse or a headset, as opposed to the
+/// batteries powering the system.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peripheral {
+    pub path: OwnedObjectPath,
+    pub kind: DeviceKind,
+    pub model: String,
+    pub percentage: f64,
+    pub state: DeviceState,
+    pub low_battery: bool,
+}
+
+impl Peripheral {
+    fn from_device(device: &DeviceProxy<'_>) -> Option<Self> {
+        let kind = DeviceKind::from(cached(device.cached_type_()));
+        let power_supply: bool = cached(device.cached_power_supply());
+        if kind == DeviceKind::LinePower || (kind == DeviceKind::Battery && power_supply) {
+            return None;
+        }
+
+        let mut model: String = cached(device.cached_model());
+        if model.is_empty() {
+            model = cached(device.cached_vendor());
+        }
+
+        Some(Self {
+            path: device.path().to_owned().into(),
+            kind,
+            model,
+            percentage: cached(device.cached_percentage()),
+            state: DeviceState::from(cached(device.cached_state())),
+            low_battery: cached::<u32>(device.cached_warning_level()) >= WARNING_LEVEL_LOW,
+        })
+    }
+}
+
+fn cached<T: Default>(value: zbus::Result<Option<T>>) -> T {
+    value.unwrap_or_default().unwrap_or_default()
+}
+
+pub fn peripheral_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
+    id: I,
+) -> iced::Subscription<PeripheralDbusEvent> {
+    subscription::channel(id, 50, move |mut output| async move {
+        let mut state = PeripheralState::Ready;
+
+        loop {
+            state = listen_peripherals(state, &mut output).await;
+        }
+    })
+}
+
+pub enum PeripheralState {
+    Ready,
+    Waiting {
+        upower: UPowerProxy<'static>,
+        devices: Vec<DeviceProxy<'static>>,
+        changes: BoxStream<'static, PeripheralChange>,
+    },
+    Finished,
+}
+
+pub enum PeripheralChange {
+    Devices,
+    Properties,
+}
+
+async fn all_devices(upower: &UPowerProxy<'static>) -> zbus::Result<Vec<DeviceProxy<'static>>> {
+    let connection = upower.connection();
+    let mut devices = Vec::new();
+    for path in upower.enumerate_devices().await? {
+        match DeviceProxy::builder(connection)
+            .path(path)?
+            .cache_properties(zbus::CacheProperties::Yes)
+            .build()
+            .await
+        {
+            Ok(device) => devices.push(device),
+            Err(err) => tracing::warn!(?err, "Failed to get UPower device"),
+        }
+    }
+    Ok(devices)
+}
+
+async fn peripheral_changes(
+    upower: &UPowerProxy<'static>,
+    devices: &[DeviceProxy<'static>],
+) -> zbus::Result<BoxStream<'static, PeripheralChange>> {
+    let mut streams = vec![
+        upower
+            .receive_device_added()
+            .await?
+            .map(|_| PeripheralChange::Devices)
+            .boxed(),
+        upower
+            .receive_device_removed()
+            .await?
+            .map(|_| PeripheralChange::Devices)
+            .boxed(),
+    ];
+    for device in devices {
+        streams.push(
+            device
+                .receive_percentage_changed()
+                .await
+                .map(|_| PeripheralChange::Properties)
+                .boxed(),
+        );
+        streams.push(
+            device
+                .receive_state_changed()
+                .await
+                .map(|_| PeripheralChange::Properties)
+                .boxed(),
+        );
+        streams.push(
+            device
+                .receive_warning_level_changed()
+                .await
+                .map(|_| PeripheralChange::Properties)
+                .boxed(),
+        );
+    }
+    Ok(futures::stream::select_all(streams).boxed())
+}
+
+async fn peripherals_state(
+    upower: UPowerProxy<'static>,
+    output: &mut futures::channel::mpsc::Sender<PeripheralDbusEvent>,
+) -> PeripheralState {
+    let devices = match all_devices(&upower).await {
+        Ok(devices) => devices,
+        Err(err) => {
+            tracing::error!(?err, "Failed to enumerate UPower devices");
+            return PeripheralState::Finished;
+        }
+    };
+    let changes = match peripheral_changes(&upower, &devices).await {
+        Ok(changes) => changes,
+        Err(err) => {
+            tracing::error!(?err, "Failed to listen for UPower device changes");
+            return PeripheralState::Finished;
+        }
+    };
+    _ = output
+        .send(PeripheralDbusEvent::Update(
+            devices.iter().filter_map(Peripheral::from_device).collect(),
+        ))
+        .await;
+    PeripheralState::Waiting {
+        upower,
+        devices,
+        changes,
+    }
+}
+
+async fn listen_peripherals(
+    state: PeripheralState,
+    output: &mut futures::channel::mpsc::Sender<PeripheralDbusEvent>,
+) -> PeripheralState {
+    match state {
+        PeripheralState::Ready => {
+            let Ok(connection) = zbus::Connection::system().await else {
+                return PeripheralState::Finished;
+            };
+            let Ok(upower) = UPowerProxy::new(&connection).await else {
+                return PeripheralState::Finished;
+            };
+            peripherals_state(upower, output).await
+        }
+        PeripheralState::Waiting {
+            upower,
+            devices,
+            mut changes,
+        } => match changes.next().await {
+            Some(PeripheralChange::Devices) => peripherals_state(upower, output).await,
+            Some(PeripheralChange::Properties) => {
+                _ = output
+                    .send(PeripheralDbusEvent::Update(
+                        devices.iter().filter_map(Peripheral::from_device).collect(),
+                    ))
+                    .await;
+                PeripheralState::Waiting {
+                    upower,
+                    devices,
+                    changes,
+                }
+            }
+            None => PeripheralState::Finished,
+        },
+        PeripheralState::Finished => iced::futures::future::pending().await,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PeripheralDbusEvent {
+    Update(Vec<Peripheral>),
+}
+
+/// Samples recorded by UPower for the system battery.
+#[derive(Debug, Clone, Default)]
+pub struct BatteryHistory {
+    /// `(unix time, percentage)`
+    pub charge: Vec<(u32, f64)>,
+    /// `(unix time, watts)`
+    pub rate: Vec<(u32, f64)>,
+    /// `(percentage, factor)` of the expected charging time spent at each percentage
+    pub charging: Vec<(f64, f64)>,
+    /// `(percentage, factor)` of the expected discharging time spent at each percentage
+    pub discharging: Vec<(f64, f64)>,
+}
+
+const HISTORY_RESOLUTION: u32 = 150;
+
+/// Get the battery history for the last `timespan` seconds.
+pub async fn battery_history(timespan: u32) -> zbus::Result<Option<BatteryHistory>> {
+    let connection = zbus::Connection::system().await?;
+    let Some(device) = system_battery(&connection).await? else {
+        return Ok(None);
+    };
+    if !device.has_history().await? {
+        return Ok(None);
+    }
+
+    let history = |samples: Vec<(u32, f64, u32)>| -> Vec<(u32, f64)> {
+        samples
+            .into_iter()
+            .map(|(time, value, _state)| (time, value))
+            .collect()
+    };
+    // Entries are indexed by percentage, with an accuracy of 0 where nothing was recorded
+    let statistics = |entries: Vec<(f64, f64)>| -> Vec<(f64, f64)> {
+        entries
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (_, accuracy))| *accuracy > 0.0)
+            .map(|(percent, (value, _))| (percent as f64, value))
+            .collect()
+    };
+
+    let mut battery_history = BatteryHistory {
+        charge: history(
+            device
+                .get_history("charge", timespan, HISTORY_RESOLUTION)
+                .await?,
+        ),
+        rate: history(
+            device
+                .get_history("rate", timespan, HISTORY_RESOLUTION)
+                .await?,
+        ),
+        ..Default::default()
+    };
+    if device.has_statistics().await? {
+        battery_history.charging = statistics(device.get_statistics("charging").await?);
+        battery_history.discharging = statistics(device.get_statistics("discharging").await?);
+    }
+    Ok(Some(battery_history))
 }
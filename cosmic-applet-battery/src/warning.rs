@@ -0,0 +1,89 @@
+//! Low and critical battery warnings.
+
+use logind_zbus::manager::ManagerProxy;
+use zbus::Connection;
+
+use crate::config::{BatteryAppletConfig, CriticalAction};
+
+/// How many percent the battery has to charge past a threshold before leaving its level,
+/// so the warning doesn't flap when the percentage hovers around the threshold.
+const HYSTERESIS: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum BatteryLevel {
+    #[default]
+    Normal,
+    Low,
+    Critical,
+}
+
+impl BatteryLevel {
+    pub fn next(self, percent: f64, on_battery: bool, config: &BatteryAppletConfig) -> Self {
+        if !on_battery {
+            return Self::Normal;
+        }
+        let low = f64::from(config.low_battery_threshold);
+        let critical = f64::from(config.critical_battery_threshold);
+        if percent <= critical || (self == Self::Critical && percent <= critical + HYSTERESIS) {
+            Self::Critical
+        } else if percent <= low || (self >= Self::Low && percent <= low + HYSTERESIS) {
+            Self::Low
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+pub async fn run_critical_action(action: CriticalAction) -> zbus::Result<()> {
+    let connection = Connection::system().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+    match action {
+        CriticalAction::Nothing => Ok(()),
+        CriticalAction::Suspend => manager_proxy.suspend(true).await,
+        CriticalAction::Hibernate => manager_proxy.hibernate(true).await,
+        CriticalAction::PowerOff => manager_proxy.power_off(true).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Low at 10% and critical at 5%
+    fn next(level: BatteryLevel, percent: f64) -> BatteryLevel {
+        level.next(percent, true, &BatteryAppletConfig::default())
+    }
+
+    #[test]
+    fn critical_until_past_hysteresis() {
+        assert_eq!(next(BatteryLevel::Critical, 6.5), BatteryLevel::Critical);
+        assert_eq!(next(BatteryLevel::Critical, 7.), BatteryLevel::Critical);
+        assert_eq!(next(BatteryLevel::Critical, 7.5), BatteryLevel::Low);
+    }
+
+    #[test]
+    fn low_until_past_hysteresis() {
+        assert_eq!(next(BatteryLevel::Low, 11.5), BatteryLevel::Low);
+        assert_eq!(next(BatteryLevel::Low, 12.5), BatteryLevel::Normal);
+        // Hysteresis only applies when leaving a level
+        assert_eq!(next(BatteryLevel::Normal, 11.), BatteryLevel::Normal);
+        assert_eq!(next(BatteryLevel::Normal, 6.5), BatteryLevel::Low);
+    }
+
+    #[test]
+    fn normal_straight_to_critical() {
+        assert_eq!(next(BatteryLevel::Normal, 4.), BatteryLevel::Critical);
+    }
+
+    #[test]
+    fn normal_on_ac() {
+        let config = BatteryAppletConfig::default();
+        for level in [
+            BatteryLevel::Normal,
+            BatteryLevel::Low,
+            BatteryLevel::Critical,
+        ] {
+            assert_eq!(level.next(1., false, &config), BatteryLevel::Normal);
+        }
+    }
+}
@@ -9,4 +9,11 @@ unsuccessful = Pairing Unsuccessful
 check-device = Make sure {$deviceName} is turned on, in range, and is ready to pair.
 try-again = Try Again
 discoverable = Discoverable
-pairable = Pairable
\ No newline at end of file
+pairable = Pairable
+trust = Trust
+untrust = Untrust
+block = Block
+unblock = Unblock
+forget = Forget
+forget-confirm = Forget {$deviceName}? You will need to pair it again to use it.
+show-battery-in-panel = Show Battery Level in Panel
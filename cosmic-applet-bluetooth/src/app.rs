@@ -1,4 +1,7 @@
 use crate::bluetooth::{BluerDeviceStatus, BluerRequest, BluerState};
+use crate::config::BluetoothAppletConfig;
+use bluer::{Address, DeviceProperty};
+use cosmic::applet::cosmic_panel_config::PanelAnchor;
 use cosmic::applet::token::subscription::{
     activation_token_subscription, TokenRequest, TokenUpdate,
 };
@@ -7,6 +10,7 @@ use cosmic::cctk::sctk::reexports::calloop;
 use cosmic::applet::{menu_button, padded_control};
 use cosmic::Command;
 use cosmic::{
+    cosmic_config::CosmicConfigEntry,
     iced::{
         self,
         wayland::popup::{destroy_popup, get_popup},
@@ -24,6 +28,7 @@ use cosmic::{
 };
 use cosmic_time::{anim, chain, id, once_cell::sync::Lazy, Instant, Timeline};
 use std::collections::HashMap;
+use std::mem;
 use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 
@@ -31,6 +36,7 @@ use crate::bluetooth::{bluetooth_subscription, BluerDevice, BluerEvent};
 use crate::{config, fl};
 
 static BLUETOOTH_ENABLED: Lazy<id::Toggler> = Lazy::new(id::Toggler::unique);
+static SHOW_BATTERY_IN_PANEL: Lazy<id::Toggler> = Lazy::new(id::Toggler::unique);
 
 pub fn run() -> cosmic::iced::Result {
     cosmic::applet::run::<CosmicBluetoothApplet>(false, ())
@@ -43,9 +49,14 @@ struct CosmicBluetoothApplet {
     popup: Option<window::Id>,
     bluer_state: BluerState,
     bluer_sender: Option<Sender<BluerRequest>>,
+    config: BluetoothAppletConfig,
     // UI state
     show_visible_devices: bool,
     request_confirmation: Option<(BluerDevice, String, Sender<bool>)>,
+    /// Device whose context menu is open.
+    device_menu: Option<Address>,
+    /// Whether the open context menu is asking to confirm forgetting the device.
+    confirm_forget: bool,
     token_tx: Option<calloop::channel::Sender<TokenRequest>>,
     timeline: Timeline,
 }
@@ -59,6 +70,136 @@ impl CosmicBluetoothApplet {
         }
         .to_string();
     }
+
+    fn write_config(&self) {
+        if let Ok(helper) =
+            cosmic::cosmic_config::Config::new(config::APP_ID, BluetoothAppletConfig::VERSION)
+        {
+            if let Err(err) = self.config.write_entry(&helper) {
+                tracing::error!(?err, "Error writing config");
+            }
+        }
+    }
+
+    // Lowest battery level of the connected devices
+    fn lowest_battery(&self) -> Option<u8> {
+        self.bluer_state
+            .devices
+            .iter()
+            .filter(|d| d.status == BluerDeviceStatus::Connected)
+            .filter_map(|d| d.battery_percentage)
+            .min()
+    }
+
+    fn set_device_property(&mut self, address: Address, property: DeviceProperty) {
+        if let Some(d) = self
+            .bluer_state
+            .devices
+            .iter_mut()
+            .find(|d| d.address == address)
+        {
+            d.properties
+                .retain(|p| mem::discriminant(p) != mem::discriminant(&property));
+            d.properties.push(property);
+        }
+    }
+
+    fn device_options(&self, dev: &BluerDevice) -> Element<Message> {
+        if self.confirm_forget {
+            return column![
+                padded_control(
+                    text(fl!(
+                        "forget-confirm",
+                        HashMap::from_iter(vec![("deviceName", dev.name.clone())])
+                    ))
+                    .horizontal_alignment(Horizontal::Left)
+                    .vertical_alignment(Vertical::Center)
+                    .width(Length::Fill)
+                    .size(14)
+                ),
+                padded_control(
+                    row![
+                        button(
+                            text(fl!("cancel"))
+                                .size(14)
+                                .width(Length::Fill)
+                                .height(Length::Fixed(24.0))
+                                .vertical_alignment(Vertical::Center)
+                        )
+                        .padding([8, 24])
+                        .on_press(Message::CancelForget),
+                        button(
+                            text(fl!("forget"))
+                                .size(14)
+                                .width(Length::Fill)
+                                .height(Length::Fixed(24.0))
+                                .vertical_alignment(Vertical::Center)
+                        )
+                        .padding([8, 24])
+                        .on_press(Message::Request(BluerRequest::RemoveDevice(dev.address))),
+                    ]
+                    .spacing(8)
+                    .align_items(Alignment::Center)
+                )
+                .align_x(Horizontal::Center)
+            ]
+            .into();
+        }
+
+        let item = |label: String, message: Message| {
+            menu_button(text(label).size(14).width(Length::Fill))
+                .padding([8, 24, 8, 52])
+                .on_press(message)
+        };
+        let trusted = dev.is_trusted();
+        let blocked = dev.is_blocked();
+        column![
+            item(
+                if trusted {
+                    fl!("untrust")
+                } else {
+                    fl!("trust")
+                },
+                Message::Request(BluerRequest::SetTrusted(dev.address, !trusted)),
+            ),
+            item(
+                if blocked {
+                    fl!("unblock")
+                } else {
+                    fl!("block")
+                },
+                Message::Request(BluerRequest::SetBlocked(dev.address, !blocked)),
+            ),
+            item(fl!("forget"), Message::ForgetDevice),
+        ]
+        .into()
+    }
+
+    // A device entry, followed by its context menu when open
+    fn device_entry<'a>(
+        &'a self,
+        dev: &'a BluerDevice,
+        entry: impl Into<Element<'a, Message>>,
+    ) -> Element<'a, Message> {
+        let entry = row![
+            entry.into(),
+            button::icon(
+                icon::from_name("view-more-symbolic")
+                    .size(16)
+                    .symbolic(true)
+            )
+            .extra_small()
+            .style(cosmic::theme::Button::AppletIcon)
+            .on_press(Message::ToggleDeviceMenu(dev.address)),
+        ]
+        .padding([0, 16, 0, 0])
+        .align_items(Alignment::Center);
+        if self.device_menu == Some(dev.address) {
+            column![entry, self.device_options(dev)].into()
+        } else {
+            entry.into()
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -75,6 +216,11 @@ enum Message {
     OpenSettings,
     Frame(Instant),
     ToggleBluetooth(chain::Toggler, bool),
+    ToggleBatteryInPanel(chain::Toggler, bool),
+    ConfigChanged(BluetoothAppletConfig),
+    ToggleDeviceMenu(Address),
+    ForgetDevice,
+    CancelForget,
 }
 
 impl cosmic::Application for CosmicBluetoothApplet {
@@ -192,6 +338,19 @@ impl cosmic::Application for CosmicBluetoothApplet {
                 BluerEvent::DevicesChanged { state } => {
                     self.bluer_state = state;
                 }
+                BluerEvent::BatteryChanged {
+                    address,
+                    percentage,
+                } => {
+                    if let Some(d) = self
+                        .bluer_state
+                        .devices
+                        .iter_mut()
+                        .find(|d| d.address == address)
+                    {
+                        d.battery_percentage = Some(percentage);
+                    }
+                }
                 BluerEvent::Finished => {
                     // TODO should this exit with an error causing a restart?
                     eprintln!("bluetooth subscription finished. exiting...");
@@ -265,6 +424,17 @@ impl cosmic::Application for CosmicBluetoothApplet {
                             d.status = BluerDeviceStatus::Pairing;
                         }
                     }
+                    BluerRequest::RemoveDevice(add) => {
+                        self.bluer_state.devices.retain(|d| d.address != *add);
+                        self.device_menu = None;
+                        self.confirm_forget = false;
+                    }
+                    BluerRequest::SetTrusted(add, trusted) => {
+                        self.set_device_property(*add, DeviceProperty::Trusted(*trusted));
+                    }
+                    BluerRequest::SetBlocked(add, blocked) => {
+                        self.set_device_property(*add, DeviceProperty::Blocked(*blocked));
+                    }
                     _ => {} // TODO
                 }
                 if let Some(tx) = self.bluer_sender.as_mut().cloned() {
@@ -287,9 +457,33 @@ impl cosmic::Application for CosmicBluetoothApplet {
                     });
                 }
             }
+            Message::ToggleBatteryInPanel(chain, enabled) => {
+                self.timeline.set_chain(chain).start();
+                self.config.show_battery_in_panel = enabled;
+                self.write_config();
+            }
+            Message::ConfigChanged(config) => {
+                self.config = config;
+            }
+            Message::ToggleDeviceMenu(address) => {
+                self.confirm_forget = false;
+                if self.device_menu == Some(address) {
+                    self.device_menu = None;
+                } else {
+                    self.device_menu = Some(address);
+                }
+            }
+            Message::ForgetDevice => {
+                self.confirm_forget = true;
+            }
+            Message::CancelForget => {
+                self.confirm_forget = false;
+            }
             Message::CloseRequested(id) => {
                 if Some(id) == self.popup {
                     self.popup = None;
+                    self.device_menu = None;
+                    self.confirm_forget = false;
                 }
             }
             Message::OpenSettings => {
@@ -337,11 +531,30 @@ impl cosmic::Application for CosmicBluetoothApplet {
     }
 
     fn view(&self) -> Element<Message> {
-        self.core
+        let btn = self
+            .core
             .applet
             .icon_button(&self.icon_name)
-            .on_press(Message::TogglePopup)
-            .into()
+            .on_press(Message::TogglePopup);
+        let Some(percentage) = self
+            .lowest_battery()
+            .filter(|_| self.config.show_battery_in_panel)
+        else {
+            return btn.into();
+        };
+        let battery = self
+            .core
+            .applet
+            .icon_button(&battery_icon_name(percentage))
+            .on_press(Message::TogglePopup);
+        match self.core.applet.anchor {
+            PanelAnchor::Left | PanelAnchor::Right => {
+                column![battery, btn].align_items(Alignment::Center).into()
+            }
+            PanelAnchor::Top | PanelAnchor::Bottom => {
+                row![battery, btn].align_items(Alignment::Center).into()
+            }
+        }
     }
 
     fn view_window(&self, _id: window::Id) -> Element<Message> {
@@ -365,6 +578,20 @@ impl cosmic::Application for CosmicBluetoothApplet {
 
             match &dev.status {
                 BluerDeviceStatus::Connected => {
+                    if let Some(percentage) = dev.battery_percentage {
+                        row = row.push(
+                            row![
+                                icon::from_name(battery_icon_name(percentage))
+                                    .size(16)
+                                    .symbolic(true),
+                                text(format!("{percentage}%"))
+                                    .size(14)
+                                    .vertical_alignment(Vertical::Center),
+                            ]
+                            .align_items(Alignment::Center)
+                            .spacing(4),
+                        );
+                    }
                     row = row.push(
                         text(fl!("connected"))
                             .size(14)
@@ -383,22 +610,25 @@ impl cosmic::Application for CosmicBluetoothApplet {
                 BluerDeviceStatus::Disconnected | BluerDeviceStatus::Pairing => continue,
             };
 
-            known_bluetooth = known_bluetooth.push(menu_button(row).on_press(match dev.status {
-                BluerDeviceStatus::Connected => {
-                    Message::Request(BluerRequest::DisconnectDevice(dev.address))
-                }
-                BluerDeviceStatus::Disconnected => {
-                    Message::Request(BluerRequest::PairDevice(dev.address))
-                }
-                BluerDeviceStatus::Paired => {
-                    Message::Request(BluerRequest::ConnectDevice(dev.address))
-                }
-                BluerDeviceStatus::Connecting => {
-                    Message::Request(BluerRequest::CancelConnect(dev.address))
-                }
-                BluerDeviceStatus::Disconnecting => Message::Ignore, // Start connecting?
-                BluerDeviceStatus::Pairing => Message::Ignore,       // Cancel pairing?
-            }));
+            let entry = menu_button(row)
+                .width(Length::Fill)
+                .on_press(match dev.status {
+                    BluerDeviceStatus::Connected => {
+                        Message::Request(BluerRequest::DisconnectDevice(dev.address))
+                    }
+                    BluerDeviceStatus::Disconnected => {
+                        Message::Request(BluerRequest::PairDevice(dev.address))
+                    }
+                    BluerDeviceStatus::Paired => {
+                        Message::Request(BluerRequest::ConnectDevice(dev.address))
+                    }
+                    BluerDeviceStatus::Connecting => {
+                        Message::Request(BluerRequest::CancelConnect(dev.address))
+                    }
+                    BluerDeviceStatus::Disconnecting => Message::Ignore, // Start connecting?
+                    BluerDeviceStatus::Pairing => Message::Ignore,       // Cancel pairing?
+                });
+            known_bluetooth = known_bluetooth.push(self.device_entry(dev, entry));
         }
 
         let mut content = column![
@@ -519,10 +749,10 @@ impl cosmic::Application for CosmicBluetoothApplet {
                 ]
                 .align_items(Alignment::Center)
                 .spacing(12);
-                visible_devices = visible_devices.push(
-                    menu_button(row.width(Length::Fill))
-                        .on_press(Message::Request(BluerRequest::PairDevice(dev.address))),
-                );
+                let entry = menu_button(row.width(Length::Fill))
+                    .width(Length::Fill)
+                    .on_press(Message::Request(BluerRequest::PairDevice(dev.address)));
+                visible_devices = visible_devices.push(self.device_entry(dev, entry));
                 visible_devices_count += 1;
             }
             list_column.push(visible_devices.into());
@@ -542,6 +772,18 @@ impl cosmic::Application for CosmicBluetoothApplet {
             content = content.push(Column::with_children(list_column));
         }
         content = content.push(padded_control(divider::horizontal::default()));
+        content = content.push(padded_control(
+            anim!(
+                //toggler
+                SHOW_BATTERY_IN_PANEL,
+                &self.timeline,
+                fl!("show-battery-in-panel"),
+                self.config.show_battery_in_panel,
+                Message::ToggleBatteryInPanel,
+            )
+            .text_size(14)
+            .width(Length::Fill),
+        ));
         content = content.push(
             menu_button(text(fl!("settings")).size(14).width(Length::Fill))
                 .on_press(Message::OpenSettings),
@@ -553,6 +795,12 @@ impl cosmic::Application for CosmicBluetoothApplet {
         Subscription::batch(vec![
             activation_token_subscription(0).map(Message::Token),
             bluetooth_subscription(0).map(Message::BluetoothEvent),
+            self.core.watch_config(config::APP_ID).map(|u| {
+                for err in u.errors {
+                    tracing::error!(?err, "Error watching config");
+                }
+                Message::ConfigChanged(u.config)
+            }),
             self.timeline
                 .as_subscription()
                 .map(|(_, now)| Message::Frame(now)),
@@ -567,3 +815,8 @@ impl cosmic::Application for CosmicBluetoothApplet {
         Some(Message::CloseRequested(id))
     }
 }
+
+// Adwaita draws the levels at 10% and below as low battery
+fn battery_icon_name(percentage: u8) -> String {
+    format!("battery-level-{}-symbolic", percentage.min(100) / 10 * 10)
+}
@@ -2,7 +2,7 @@ use std::{collections::HashMap, fmt::Debug, hash::Hash, mem, sync::Arc, time::Du
 
 use bluer::{
     agent::{Agent, AgentHandle},
-    Adapter, Address, DeviceProperty, Session, Uuid,
+    Adapter, AdapterEvent, Address, DeviceEvent, DeviceProperty, Session, Uuid,
 };
 use cosmic::iced::{
     self,
@@ -111,6 +111,17 @@ async fn start_listening(
                     BluerSessionEvent::AgentEvent(e) => {
                         _ = output.send(BluerEvent::AgentEvent(e)).await;
                     }
+                    BluerSessionEvent::BatteryChanged {
+                        address,
+                        percentage,
+                    } => {
+                        _ = output
+                            .send(BluerEvent::BatteryChanged {
+                                address,
+                                percentage,
+                            })
+                            .await;
+                    }
                     _ => {}
                 }
             } else {
@@ -133,6 +144,10 @@ pub enum BluerRequest {
     ConnectDevice(Address),
     DisconnectDevice(Address),
     CancelConnect(Address),
+    /// Forget a device, removing its pairing.
+    RemoveDevice(Address),
+    SetTrusted(Address, bool),
+    SetBlocked(Address, bool),
     StateUpdate,
 }
 
@@ -151,6 +166,10 @@ pub enum BluerEvent {
         state: BluerState,
     },
     AgentEvent(BluerAgentEvent),
+    BatteryChanged {
+        address: Address,
+        percentage: u8,
+    },
     Finished,
 }
 
@@ -181,6 +200,8 @@ pub struct BluerDevice {
     pub status: BluerDeviceStatus,
     pub properties: Vec<DeviceProperty>,
     pub icon: String,
+    /// Battery level reported through the `org.bluez.Battery1` interface.
+    pub battery_percentage: Option<u8>,
 }
 
 impl Eq for BluerDevice {}
@@ -241,6 +262,13 @@ impl BluerDevice {
                 }
             })
             .unwrap_or_else(|| "bluetooth-symbolic".into());
+        let battery_percentage = properties.iter().find_map(|p| {
+            if let DeviceProperty::BatteryPercentage(percentage) = p {
+                Some(*percentage)
+            } else {
+                None
+            }
+        });
 
         Self {
             name,
@@ -248,9 +276,22 @@ impl BluerDevice {
             status,
             properties,
             icon,
+            battery_percentage,
         }
     }
 
+    pub fn is_trusted(&self) -> bool {
+        self.properties
+            .iter()
+            .any(|p| matches!(p, DeviceProperty::Trusted(true)))
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.properties
+            .iter()
+            .any(|p| matches!(p, DeviceProperty::Blocked(true)))
+    }
+
     fn paired_and_trusted(&self) -> bool {
         self.properties
             .iter()
@@ -275,6 +316,10 @@ pub enum BluerSessionEvent {
     ChangesProcessed(BluerState),
     ChangeStreamEnded, // TODO can we just restart the stream in a new task?
     AgentEvent(BluerAgentEvent),
+    BatteryChanged {
+        address: Address,
+        percentage: u8,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -493,6 +538,7 @@ impl BluerSessionState {
         self_.process_requests(req_rx);
         self_.process_changes();
         self_.listen_bluetooth_power_changes();
+        self_.listen_battery_changes();
 
         Ok(self_)
     }
@@ -528,6 +574,46 @@ impl BluerSessionState {
         });
     }
 
+    // Battery levels change without the device list changing, so each device is watched on its own
+    fn listen_battery_changes(&self) {
+        let tx = self.tx.clone();
+        let adapter_clone = self.adapter.clone();
+        let _handle: JoinHandle<anyhow::Result<()>> = spawn(async move {
+            // Subscribe before listing the devices, so none added in between are missed
+            let mut adapter_events = Box::pin(adapter_clone.events().await?);
+            let mut watchers: HashMap<Address, JoinHandle<()>> = HashMap::new();
+            for address in adapter_clone.device_addresses().await.unwrap_or_default() {
+                if let Some(handle) = watch_battery(&adapter_clone, address, tx.clone()).await {
+                    watchers.insert(address, handle);
+                }
+            }
+
+            while let Some(event) = adapter_events.next().await {
+                match event {
+                    AdapterEvent::DeviceAdded(address) if !watchers.contains_key(&address) => {
+                        if let Some(handle) =
+                            watch_battery(&adapter_clone, address, tx.clone()).await
+                        {
+                            watchers.insert(address, handle);
+                        }
+                    }
+                    AdapterEvent::DeviceRemoved(address) => {
+                        if let Some(handle) = watchers.remove(&address) {
+                            handle.abort();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            // The stream ends when the adapter is removed
+            for handle in watchers.into_values() {
+                handle.abort();
+            }
+            Ok(())
+        });
+    }
+
     // Note: For some reason, this doesn't actually seem to work so well. it seems unreliable...
     pub(crate) fn process_changes(&self) {
         let tx = self.tx.clone();
@@ -657,6 +743,34 @@ impl BluerSessionState {
                                 err_msg = Some("No active connection request found".to_string());
                             }
                         }
+                        BluerRequest::RemoveDevice(address) => {
+                            let res = adapter_clone.remove_device(*address).await;
+                            if let Err(err) = res {
+                                err_msg = Some(err.to_string());
+                            }
+                        }
+                        BluerRequest::SetTrusted(address, trusted) => {
+                            let res = adapter_clone.device(*address);
+                            if let Err(err) = res {
+                                err_msg = Some(err.to_string());
+                            } else if let Ok(device) = res {
+                                let res = device.set_trusted(*trusted).await;
+                                if let Err(err) = res {
+                                    err_msg = Some(err.to_string());
+                                }
+                            }
+                        }
+                        BluerRequest::SetBlocked(address, blocked) => {
+                            let res = adapter_clone.device(*address);
+                            if let Err(err) = res {
+                                err_msg = Some(err.to_string());
+                            } else if let Ok(device) = res {
+                                let res = device.set_blocked(*blocked).await;
+                                if let Err(err) = res {
+                                    err_msg = Some(err.to_string());
+                                }
+                            }
+                        }
                         BluerRequest::StateUpdate => {}
                         BluerRequest::SetPairable(enabled) => {
                             let res = adapter_clone.set_pairable(*enabled).await;
@@ -709,6 +823,28 @@ impl BluerSessionState {
     }
 }
 
+// Send the battery level of the device at `address` whenever it changes
+async fn watch_battery(
+    adapter: &Adapter,
+    address: Address,
+    tx: Sender<BluerSessionEvent>,
+) -> Option<JoinHandle<()>> {
+    let device = adapter.device(address).ok()?;
+    let mut events = Box::pin(device.events().await.ok()?);
+    Some(spawn(async move {
+        while let Some(DeviceEvent::PropertyChanged(property)) = events.next().await {
+            if let DeviceProperty::BatteryPercentage(percentage) = property {
+                let _ = tx
+                    .send(BluerSessionEvent::BatteryChanged {
+                        address,
+                        percentage,
+                    })
+                    .await;
+            }
+        }
+    }))
+}
+
 async fn build_device_list(adapter: &Adapter) -> Vec<BluerDevice> {
     let addrs = adapter.device_addresses().await.unwrap_or_default();
     let mut devices = Vec::with_capacity(addrs.len());